@@ -0,0 +1,29 @@
+#!/bin/sh
+#  Fetches the dictionaries of zxcvbn (https://github.com/dropbox/zxcvbn, MIT licensed): its
+#  30000 most common passwords and 30000 most common English words (from Wikipedia), most
+#  common first, one per line. Point the [strength] section of the configuration at them.
+#
+#  Usage: data/fetch-dictionaries.sh [directory]    (default: data/zxcvbn)
+set -eu
+
+VERSION=4.4.2
+DIR=${1:-data/zxcvbn}
+TMP=$(mktemp -d)
+trap 'rm -rf "$TMP"' EXIT
+
+curl -fsSL "https://registry.npmjs.org/zxcvbn/-/zxcvbn-$VERSION.tgz" | tar -xz -C "$TMP"
+mkdir -p "$DIR"
+
+# The lists are comma-separated strings in lib/frequency_lists.js, e.g. passwords:"123456,...":
+extract() {
+    grep -o "$1:\"[^\"]*\"" "$TMP/package/lib/frequency_lists.js" |
+        cut -d '"' -f 2 | tr ',' '\n' > "$DIR/$2"
+    if [ ! -s "$DIR/$2" ]; then
+        echo "Could not find the $1 list in zxcvbn $VERSION." >&2
+        exit 1
+    fi
+    echo "$DIR/$2: $(wc -l < "$DIR/$2") entries."
+}
+extract passwords passwords.txt
+extract english_wikipedia english.txt
+cp "$TMP"/package/LICENSE* "$DIR/LICENSE.txt"
@@ -0,0 +1,247 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::commands::push_lines;
+use crate::config::AlertsConfig;
+use crate::duration::unix_time;
+use crate::error::Result;
+use serenity::builder::CreateMessage;
+use serenity::http::Http;
+use serenity::model::id::UserId;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tracing::error;
+
+/// An attempt to use a command without being authorized to.
+pub struct Attempt {
+    pub user_id: u64,
+    pub user_name: String,
+    pub guild_id: Option<u64>,
+    pub channel_id: u64,
+    pub command: String,
+}
+
+// Attempts by the same user, at the same command and place, are reported together:
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    user_id: u64,
+    guild_id: Option<u64>,
+    channel_id: u64,
+    command: String,
+}
+
+struct Seen {
+    user_name: String,
+    count: u32,
+    // Seconds since the Unix epoch:
+    first: u64,
+    last: u64,
+}
+
+// Attempts gathered for one alert:
+type Batch = BTreeMap<Key, Seen>;
+
+// Adds attempts to a batch, merging them with the ones already there:
+fn merge(batch: &mut Batch, key: Key, seen: Seen) {
+    match batch.get_mut(&key) {
+        Some(known) => {
+            known.count += seen.count;
+            known.first = known.first.min(seen.first);
+            known.last = known.last.max(seen.last);
+        }
+        None => {
+            batch.insert(key, seen);
+        }
+    }
+}
+
+// Describes the attempts of a batch, as the text of an alert:
+fn describe(batch: &Batch) -> String {
+    let total: u32 = batch.values().map(|seen| seen.count).sum();
+    let mut alert = format!("{total} unauthorized attempt(s) to use me:\n");
+    let lines = batch.iter().map(|(key, seen)| {
+        // Discord renders <t:...> as a timestamp in the reader's time zone:
+        let when = match seen.count {
+            1 => format!("at <t:{}:f>", seen.first),
+            n => format!(
+                "{n} times, between <t:{}:f> and <t:{}:f>",
+                seen.first, seen.last
+            ),
+        };
+        let place = match key.guild_id {
+            Some(guild_id) => format!("in <#{}> (guild `{guild_id}`)", key.channel_id),
+            None => "in a direct message".to_string(),
+        };
+        format!(
+            "- {} (`{}`) tried `/{}` {place}, {when}\n",
+            seen.user_name, key.user_id, key.command
+        )
+    });
+    push_lines(&mut alert, lines);
+    alert
+}
+
+/// Tells the owners about unauthorized attempts, by direct message. Attempts are gathered
+/// and sent together every `window_secs`, so a persistent attacker can't flood the owners.
+pub struct Alerts {
+    config: AlertsConfig,
+    // Attempts not yet sent, by owner, so one owner's DMs failing doesn't hold up the others:
+    pending: Mutex<BTreeMap<u64, Batch>>,
+}
+
+impl Alerts {
+    pub fn new(config: AlertsConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Queues the attempt for the next alert.
+    pub fn report(&self, attempt: Attempt) {
+        self.report_at(attempt, unix_time().as_secs());
+    }
+
+    fn report_at(&self, attempt: Attempt, now: u64) {
+        let mut pending = self.pending.lock().unwrap();
+        for &owner in &self.config.owners {
+            let key = Key {
+                user_id: attempt.user_id,
+                guild_id: attempt.guild_id,
+                channel_id: attempt.channel_id,
+                command: attempt.command.clone(),
+            };
+            let seen = Seen {
+                user_name: attempt.user_name.clone(),
+                count: 1,
+                first: now,
+                last: now,
+            };
+            merge(pending.entry(owner).or_default(), key, seen);
+        }
+    }
+
+    // Takes the attempts queued for an owner (if there were any):
+    fn take(&self, owner: u64) -> Option<Batch> {
+        let batch = self.pending.lock().unwrap().remove(&owner)?;
+        (!batch.is_empty()).then_some(batch)
+    }
+
+    // Queues attempts that couldn't be sent for the next alert, along with any new ones:
+    fn put_back(&self, owner: u64, batch: Batch) {
+        let mut pending = self.pending.lock().unwrap();
+        let queued = pending.entry(owner).or_default();
+        for (key, seen) in batch {
+            merge(queued, key, seen);
+        }
+    }
+
+    async fn send(&self, http: &Http, owner: u64, alert: &str) -> Result<()> {
+        let channel = UserId::new(owner).create_dm_channel(http).await?;
+        channel
+            .id
+            .send_message(http, CreateMessage::new().content(alert))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Periodically sends the owners the attempts gathered since the last alert. Attempts that
+/// couldn't be sent to an owner are retried with the next alert.
+pub async fn deliver(alerts: Arc<Alerts>, http: Arc<Http>) {
+    let mut interval = tokio::time::interval(alerts.config.window());
+    loop {
+        interval.tick().await;
+        for &owner in &alerts.config.owners {
+            let Some(batch) = alerts.take(owner) else {
+                continue;
+            };
+            if let Err(why) = alerts.send(&http, owner, &describe(&batch)).await {
+                error!(%why, owner, "Could not alert an owner.");
+                alerts.put_back(owner, batch);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alerts() -> Alerts {
+        Alerts::new(AlertsConfig {
+            owners: vec![1, 2],
+            window_secs: 60,
+        })
+    }
+
+    fn attempt(user_id: u64, command: &str) -> Attempt {
+        Attempt {
+            user_id,
+            user_name: format!("user{user_id}"),
+            guild_id: Some(10),
+            channel_id: 20,
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn repeated_attempts_are_reported_together() {
+        let alerts = alerts();
+        alerts.report_at(attempt(7, "pswd"), 100);
+        alerts.report_at(attempt(7, "pswd"), 160);
+        alerts.report_at(attempt(7, "hints"), 130);
+        alerts.report_at(attempt(8, "pswd"), 140);
+
+        let alert = describe(&alerts.take(1).unwrap());
+        assert!(alert.starts_with("4 unauthorized attempt(s)"));
+        assert_eq!(alert.lines().count(), 4);
+        assert!(alert.contains(
+            "tried `/pswd` in <#20> (guild `10`), 2 times, between <t:100:f> and <t:160:f>"
+        ));
+        assert!(alert.contains("user8 (`8`) tried `/pswd` in <#20> (guild `10`), at <t:140:f>"));
+        assert!(alerts.take(1).is_none());
+    }
+
+    #[test]
+    fn every_owner_gets_every_attempt() {
+        let alerts = alerts();
+        alerts.report_at(attempt(7, "pswd"), 100);
+        assert_eq!(alerts.take(1).unwrap().len(), 1);
+        assert_eq!(alerts.take(2).unwrap().len(), 1);
+        assert!(alerts.take(3).is_none());
+    }
+
+    #[test]
+    fn unsent_attempts_are_merged_into_the_next_alert() {
+        let alerts = alerts();
+        alerts.report_at(attempt(7, "pswd"), 100);
+        let batch = alerts.take(1).unwrap();
+        // More attempts come in while sending fails:
+        alerts.report_at(attempt(7, "pswd"), 200);
+        alerts.put_back(1, batch);
+
+        let alert = describe(&alerts.take(1).unwrap());
+        assert!(alert.starts_with("2 unauthorized attempt(s)"));
+        assert!(alert.contains("2 times, between <t:100:f> and <t:200:f>"));
+        // The other owner's alert isn't affected:
+        assert!(alerts.take(2).unwrap().values().all(|seen| seen.count == 2));
+    }
+}
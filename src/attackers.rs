@@ -0,0 +1,413 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::commands::cracktime::PARAMETERS;
+use crate::config::CracktimeConfig;
+use crate::duration::YEAR;
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::{collections::HashSet, fs::read_to_string};
+
+// The models shipped with the bot, used unless the configuration points to other ones:
+const DEFAULT_MODELS: &str = include_str!("../data/attackers.toml");
+
+// For models files that don't set it. Average US retail price, as of 2024:
+//  * https://www.eia.gov/electricity/monthly/epm_table_grapher.php?t=epmt_5_6_a
+fn default_price_per_kwh() -> f64 {
+    0.13
+}
+
+// Moore's law, roughly. Hashing speeds have long kept up with it:
+//  * https://en.wikipedia.org/wiki/Moore%27s_law
+fn default_doubling_years() -> f64 {
+    2.0
+}
+
+/// How the cost of a hash grows with one of its parameters.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    /// Proportionally (e.g. PBKDF2's iterations).
+    Linear,
+    /// Doubling with every unit (e.g. bcrypt's cost).
+    Exponential,
+}
+
+/// A tunable parameter of a hash algorithm.
+#[derive(Deserialize)]
+pub struct Parameter {
+    /// Also the name of the /cracktime option setting it.
+    pub name: String,
+    /// The value `relative_cost` was measured with.
+    pub reference: f64,
+    pub scaling: Scaling,
+}
+
+/// A hash algorithm protecting the password.
+#[derive(Deserialize)]
+pub struct Hash {
+    pub id: String,
+    pub name: String,
+    /// Time to compute one hash (with the reference parameters), relative to SHA-256.
+    pub relative_cost: f64,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    /// Whether to report this hash when none is chosen.
+    #[serde(default)]
+    pub default: bool,
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// A hash algorithm, with values for its parameters.
+pub struct Setup<'a> {
+    pub hash: &'a Hash,
+    values: Vec<f64>,
+}
+
+impl Hash {
+    /// Sets up the hash with the given parameter values, by parameter name. Missing ones take
+    /// their reference value.
+    pub fn with<'a>(&'a self, values: &[(&str, f64)]) -> Setup<'a> {
+        let values = self
+            .parameters
+            .iter()
+            .map(|parameter| {
+                values
+                    .iter()
+                    .find(|(name, _)| *name == parameter.name)
+                    .map_or(parameter.reference, |(_, value)| *value)
+            })
+            .collect();
+        Setup { hash: self, values }
+    }
+}
+
+impl Setup<'_> {
+    /// Time to compute one hash, relative to SHA-256.
+    pub fn cost(&self) -> f64 {
+        let parameters = self.hash.parameters.iter().zip(&self.values);
+        parameters.fold(
+            self.hash.relative_cost,
+            |cost, (parameter, value)| match parameter.scaling {
+                Scaling::Linear => cost * value / parameter.reference,
+                Scaling::Exponential => cost * 2f64.powf(value - parameter.reference),
+            },
+        )
+    }
+
+    /// The name of the hash, with its parameters (e.g. "bcrypt (cost 10)").
+    pub fn name(&self) -> String {
+        if self.values.is_empty() {
+            return self.hash.name.clone();
+        }
+        let parameters: Vec<String> = (self.hash.parameters.iter().zip(&self.values))
+            .map(|(parameter, value)| format!("{} {value}", parameter.name))
+            .collect();
+        format!("{} ({})", self.hash.name, parameters.join(", "))
+    }
+}
+
+/// Hardware trying to crack the password.
+#[derive(Deserialize)]
+pub struct Hardware {
+    pub id: String,
+    pub description: String,
+    /// SHA-256 hashes computed per second.
+    pub sha256_rate: f64,
+    /// When the figures were true.
+    #[serde(default)]
+    pub date: Option<String>,
+    /// Price of buying the hardware, in US dollars.
+    #[serde(default)]
+    pub price: Option<f64>,
+    /// Power drawn by the hardware while cracking, in watts.
+    #[serde(default)]
+    pub power_watts: Option<f64>,
+    /// Price of renting the hardware (e.g. in the cloud) for an hour, in US dollars.
+    #[serde(default)]
+    pub rental_per_hour: Option<f64>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// What cracking a password within some time would take, with some hardware.
+pub struct Costs {
+    /// How many of the hardware are needed to make it in time.
+    pub copies: f64,
+    /// Price of buying them, in US dollars.
+    pub capital: Option<f64>,
+    /// Electricity they use, in kWh.
+    pub energy_kwh: Option<f64>,
+    /// Price of that electricity, in US dollars.
+    pub electricity: Option<f64>,
+    /// Price of renting them instead, in US dollars.
+    pub rental: Option<f64>,
+}
+
+/// Guessing through a live service (e.g. a login form), where the hash doesn't matter but the
+/// service limits how fast guesses can be made.
+#[derive(Deserialize)]
+pub struct Online {
+    pub id: String,
+    pub description: String,
+    /// Guesses made per second.
+    pub rate: f64,
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// Every hash algorithm, hardware profile and online scenario /cracktime knows about.
+#[derive(Deserialize)]
+pub struct Models {
+    #[serde(rename = "hash")]
+    pub hashes: Vec<Hash>,
+    pub hardware: Vec<Hardware>,
+    #[serde(default)]
+    pub online: Vec<Online>,
+    /// Price of electricity, in US dollars per kWh.
+    #[serde(default = "default_price_per_kwh")]
+    pub price_per_kwh: f64,
+    /// Years it takes for the hardware to get twice as fast (for the same price).
+    #[serde(default = "default_doubling_years")]
+    pub doubling_years: f64,
+}
+
+impl Models {
+    /// Loads the models from the configured file, or the ones shipped with the bot.
+    pub fn load(config: &CracktimeConfig) -> Result<Self> {
+        let models = match config.models_file.as_deref() {
+            Some(path) => read_to_string(path).map_err(Error::io(format!(
+                "Could not read attacker models file {path}"
+            )))?,
+            None => DEFAULT_MODELS.to_string(),
+        };
+        let mut models: Self = toml::from_str(&models)?;
+        if let Some(price) = config.price_per_kwh {
+            models.price_per_kwh = price;
+        }
+        if let Some(years) = config.doubling_years {
+            models.doubling_years = years;
+        }
+        if models.doubling_years <= 0.0 {
+            return Err(Error::InvalidModels(
+                "doubling_years must be positive".to_string(),
+            ));
+        }
+
+        // IDs name the models in commands' options, so they must be unique:
+        let mut ids = HashSet::new();
+        for id in models.hashes.iter().map(|hash| &hash.id) {
+            if !ids.insert(("hash", id)) {
+                return Err(Error::InvalidModels(format!("duplicate hash ID {id}")));
+            }
+        }
+        for id in models.hardware.iter().map(|hardware| &hardware.id) {
+            if !ids.insert(("hardware", id)) {
+                return Err(Error::InvalidModels(format!("duplicate hardware ID {id}")));
+            }
+        }
+        for id in models.online.iter().map(|online| &online.id) {
+            if !ids.insert(("online", id)) {
+                return Err(Error::InvalidModels(format!("duplicate online ID {id}")));
+            }
+        }
+
+        // Parameters are set through options of the same name, so there must be one:
+        for hash in &models.hashes {
+            for parameter in &hash.parameters {
+                if !PARAMETERS.iter().any(|&(name, ..)| name == parameter.name) {
+                    return Err(Error::InvalidModels(format!(
+                        "hash {} has parameter {}, which can't be set",
+                        hash.id, parameter.name
+                    )));
+                }
+            }
+        }
+        Ok(models)
+    }
+
+    /// Seconds needed to make the given number of guesses at today's guess rate, if the attack
+    /// starts `years_ahead` years from now, and the attacker keeps upgrading their hardware
+    /// as it gets faster.
+    pub fn projected_time(&self, guesses: f64, rate: f64, years_ahead: f64) -> f64 {
+        // The rate doubles every `doubling_years`: r(t) = r0 * 2^(t / d). The guesses made
+        // from time a to a + T are its integral, r0 * d / ln 2 * 2^(a / d) * (2^(T / d) - 1),
+        // which solved for T gives:
+        let doubling = self.doubling_years * YEAR;
+        let start_rate = rate * 2f64.powf(years_ahead * YEAR / doubling);
+        doubling / 2f64.ln() * (guesses * 2f64.ln() / (start_rate * doubling)).ln_1p()
+    }
+
+    /// Guesses (as a power of two) that can be made in the given number of seconds at today's
+    /// guess rate, if the attack starts `years_ahead` years from now (the inverse of
+    /// `projected_time`). Kept in powers of two, as they overflow after a few thousand years.
+    pub fn projected_guesses_log2(&self, seconds: f64, rate: f64, years_ahead: f64) -> f64 {
+        // log2(2^x - 1) = x + log2(1 - 2^-x), with x the doublings during the attack:
+        let doubling = self.doubling_years * YEAR;
+        let doublings = seconds / doubling;
+        let start_rate_log2 = rate.log2() + years_ahead * YEAR / doubling;
+        start_rate_log2
+            + (doubling / 2f64.ln()).log2()
+            + doublings
+            + (-(-doublings * 2f64.ln()).exp_m1()).log2()
+    }
+
+    pub fn hash(&self, id: &str) -> Option<&Hash> {
+        self.hashes.iter().find(|hash| hash.id == id)
+    }
+
+    pub fn hardware(&self, id: &str) -> Option<&Hardware> {
+        self.hardware.iter().find(|hardware| hardware.id == id)
+    }
+
+    /// Every source cited by the models, without repetitions.
+    pub fn sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = Vec::new();
+        let cited = (self.hashes.iter().flat_map(|hash| &hash.sources))
+            .chain(self.hardware.iter().flat_map(|hw| &hw.sources))
+            .chain(self.online.iter().flat_map(|online| &online.sources));
+        for source in cited {
+            if !sources.contains(&source.as_str()) {
+                sources.push(source);
+            }
+        }
+        sources
+    }
+}
+
+impl Hardware {
+    /// Guesses per second against the given hash.
+    pub fn guess_rate(&self, hash: &Setup) -> f64 {
+        self.sha256_rate / hash.cost()
+    }
+
+    /// What cracking a password within the given time would take, if one of this hardware
+    /// needs `seconds` for it. Buying is done in whole copies, while electricity and rental
+    /// are only paid for the time they are actually cracking.
+    pub fn costs(&self, seconds: f64, within: f64, price_per_kwh: f64) -> Costs {
+        let copies = (seconds / within).ceil().max(1.0);
+        let energy_kwh = self
+            .power_watts
+            .map(|watts| watts * seconds / 3600.0 / 1000.0);
+        Costs {
+            copies,
+            capital: self.price.map(|price| price * copies),
+            energy_kwh,
+            electricity: energy_kwh.map(|kwh| kwh * price_per_kwh),
+            rental: self.rental_per_hour.map(|price| price * seconds / 3600.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models() -> Models {
+        Models::load(&CracktimeConfig::default()).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-6,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn shipped_models_load() {
+        let models = models();
+        assert!(models.hashes.iter().any(|hash| hash.default));
+        assert!(!models.hardware.is_empty());
+        assert!(!models.online.is_empty());
+    }
+
+    #[test]
+    fn short_attacks_barely_speed_up() {
+        // A minute's worth of guesses, with hardware that doubles its speed every two years:
+        let models = models();
+        let rate = 1e9;
+        assert_close(models.projected_time(60.0 * rate, rate, 0.0), 60.0);
+        // Starting one doubling later, it takes half as long:
+        assert_close(
+            models.projected_time(60.0 * rate, rate, models.doubling_years),
+            30.0,
+        );
+    }
+
+    #[test]
+    fn long_attacks_speed_up() {
+        // Guesses for a thousand years at today's rate take far less with ever faster hardware:
+        let models = models();
+        let rate = 1e9;
+        let projected = models.projected_time(1000.0 * YEAR * rate, rate, 0.0);
+        assert!(projected < 20.0 * YEAR);
+        assert!(projected > 10.0 * YEAR);
+    }
+
+    #[test]
+    fn projections_are_inverse() {
+        let models = models();
+        let rate = 2.5e10;
+        for (guesses, years_ahead) in [(1e12, 0.0), (1e20, 10.0), (1e30, 3.0), (1e40, 50.0)] {
+            let seconds = models.projected_time(guesses, rate, years_ahead);
+            assert_close(
+                models.projected_guesses_log2(seconds, rate, years_ahead),
+                guesses.log2(),
+            );
+        }
+    }
+
+    #[test]
+    fn projected_guesses_do_not_overflow() {
+        // Ten thousand years of doubling every two years: 2^5000 guesses, well beyond f64:
+        let models = models();
+        let guesses_log2 = models.projected_guesses_log2(1e4 * YEAR, 1e9, 0.0);
+        assert!(guesses_log2.is_finite());
+        assert!(guesses_log2 > 5000.0);
+    }
+
+    #[test]
+    fn unsettable_parameters_are_rejected() {
+        let dir = std::env::temp_dir().join("mother-brain-attackers");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("attackers.toml");
+        std::fs::write(
+            &path,
+            r#"
+            hardware = []
+
+            [[hash]]
+            id = "yescrypt"
+            name = "yescrypt"
+            relative_cost = 1e6
+            parameters = [{ name = "rounds", reference = 5000, scaling = "linear" }]
+            "#,
+        )
+        .unwrap();
+        let config = CracktimeConfig {
+            models_file: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Models::load(&config),
+            Err(Error::InvalidModels(_))
+        ));
+    }
+}
@@ -0,0 +1,352 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::commands::input_text;
+use crate::config::AuditConfig;
+use crate::duration::unix_time;
+use crate::error::{Error, Result};
+use crate::storage::write_atomically;
+use openssl::rand::rand_bytes;
+use openssl::sha::{Sha256, Sha512};
+use serde::{Deserialize, Serialize};
+use serenity::model::application::{Interaction, ResolvedOption, ResolvedValue};
+use std::{
+    fs::{read, read_to_string, write, OpenOptions},
+    io::{ErrorKind, Write},
+    sync::Mutex,
+};
+
+// The `prev` of the first entry:
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000\
+                       0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An entry of the audit log, one per line (as JSON).
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    // Seconds since the Unix epoch:
+    timestamp: u64,
+    user_id: u64,
+    // Where denied attempts were made from (DMs have no guild):
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    guild_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel_id: Option<u64>,
+    command: String,
+    outcome: String,
+    // Salted hash of the hint involved, if any, so the same hint can be told apart without
+    // revealing it:
+    hint_hash: Option<String>,
+    // SHA-512 of the previous line, chaining the entries together:
+    prev: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha512(line: &str) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(line.as_bytes());
+    hex(&hasher.finish())
+}
+
+// The head file keeps the number of entries and the hash of the last one, so truncating the
+// log can be detected too.
+fn read_head(path: &str) -> Result<Option<(u64, String)>> {
+    let head = match read_to_string(path) {
+        Ok(head) => head,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::io("Could not read the audit log head")(err)),
+    };
+    match head.trim().split_once(' ') {
+        Some((count, hash)) => match count.parse() {
+            Ok(count) => Ok(Some((count, hash.to_string()))),
+            Err(_) => Err(Error::InvalidAuditLog("the head is malformed".to_string())),
+        },
+        None => Err(Error::InvalidAuditLog("the head is malformed".to_string())),
+    }
+}
+
+/// Where an interaction was made from: its guild (if any) and channel.
+pub type Location = (Option<u64>, u64);
+
+// A crash between appending an entry and writing the head leaves one entry the head doesn't
+// cover yet. Accepts it if it follows the head, so the chain goes on from it:
+fn reconcile(log: &str, head: (u64, String)) -> (u64, String) {
+    let Some(last) = log.lines().last() else {
+        return head;
+    };
+    match serde_json::from_str::<Entry>(last) {
+        Ok(entry) if entry.prev == head.1 => (head.0 + 1, sha512(last)),
+        _ => head,
+    }
+}
+
+/// Append-only log of who used which command, when, and how it went. Every entry includes the
+/// SHA-512 of the previous one, so editing or removing entries breaks the chain.
+pub struct AuditLog {
+    config: AuditConfig,
+    salt: Vec<u8>,
+    // Number of entries and hash of the last one:
+    head: Mutex<(u64, String)>,
+}
+
+impl AuditLog {
+    /// Opens the audit log, creating it (and the deployment's salt) if it doesn't exist yet.
+    pub fn open(config: AuditConfig) -> Result<Self> {
+        let salt = match read(&config.salt_file) {
+            Ok(salt) => salt,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let mut salt = vec![0; 32];
+                rand_bytes(&mut salt)?;
+                write(&config.salt_file, &salt)
+                    .map_err(Error::io("Could not write the audit log salt"))?;
+                salt
+            }
+            Err(err) => return Err(Error::io("Could not read the audit log salt")(err)),
+        };
+
+        let log = match read_to_string(&config.file) {
+            Ok(log) => log,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(Error::io("Could not read the audit log")(err)),
+        };
+        let head = match read_head(&config.head_file())? {
+            Some(head) => reconcile(&log, head),
+            // Without a head, the log must be new (or have just its first entry, if the bot
+            // crashed before writing the head); anything else looks like tampering:
+            None if log.lines().count() <= 1 => reconcile(&log, (0, GENESIS.to_string())),
+            None => return Err(Error::InvalidAuditLog("the head is missing".to_string())),
+        };
+        write_head(&config, &head)?;
+
+        Ok(Self {
+            config,
+            salt,
+            head: Mutex::new(head),
+        })
+    }
+
+    fn hint_hash(&self, hint: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update(hint.as_bytes());
+        hex(&hasher.finish())
+    }
+
+    /// Appends an entry to the log. Denied attempts also record where they were made from.
+    pub fn record(
+        &self,
+        user_id: u64,
+        command: &str,
+        outcome: &str,
+        hint: Option<&str>,
+        location: Option<Location>,
+    ) -> Result<()> {
+        let mut head = self.head.lock().unwrap();
+        let entry = Entry {
+            timestamp: unix_time().as_secs(),
+            user_id,
+            guild_id: location.and_then(|(guild_id, _)| guild_id),
+            channel_id: location.map(|(_, channel_id)| channel_id),
+            command: command.to_string(),
+            outcome: outcome.to_string(),
+            hint_hash: hint.map(|hint| self.hint_hash(hint)),
+            prev: head.1.clone(),
+        };
+        let line = serde_json::to_string(&entry)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.file)
+            .map_err(Error::io("Could not write the audit log"))?;
+        let len = file
+            .metadata()
+            .map_err(Error::io("Could not write the audit log"))?
+            .len();
+        writeln!(file, "{line}").map_err(Error::io("Could not write the audit log"))?;
+
+        // The entry only counts once the head covers it. If the head can't be written, take
+        // the entry back, so the log and its head still match:
+        let next = (head.0 + 1, sha512(&line));
+        if let Err(err) = write_head(&self.config, &next) {
+            let _ = file.set_len(len);
+            return Err(err);
+        }
+        *head = next;
+        Ok(())
+    }
+}
+
+fn write_head(config: &AuditConfig, head: &(u64, String)) -> Result<()> {
+    write_atomically(
+        config.head_file(),
+        "Could not write the audit log head",
+        |file| writeln!(file, "{} {}", head.0, head.1),
+    )
+}
+
+/// Checks the chain of the audit log, and that it ends where its head says. Returns the
+/// number of entries.
+pub fn verify(config: &AuditConfig) -> Result<u64> {
+    let log = match read_to_string(&config.file) {
+        Ok(log) => log,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Error::io("Could not read the audit log")(err)),
+    };
+
+    let mut count = 0;
+    let mut prev = GENESIS.to_string();
+    for (i, line) in log.lines().enumerate() {
+        let entry: Entry = serde_json::from_str(line)
+            .map_err(|_| Error::InvalidAuditLog(format!("entry {} is malformed", i + 1)))?;
+        if entry.prev != prev {
+            return Err(Error::InvalidAuditLog(format!(
+                "entry {} does not follow the previous one",
+                i + 1
+            )));
+        }
+        prev = sha512(line);
+        count += 1;
+    }
+
+    match read_head(&config.head_file())? {
+        Some(head) if head == (count, prev) => Ok(count),
+        None if count == 0 => Ok(0),
+        Some((expected, _)) if expected > count => Err(Error::InvalidAuditLog(format!(
+            "it was truncated: {count} entries left of {expected}"
+        ))),
+        Some(_) => Err(Error::InvalidAuditLog(
+            "its last entry does not match the head".to_string(),
+        )),
+        None => Err(Error::InvalidAuditLog("the head is missing".to_string())),
+    }
+}
+
+/// The hint an interaction is about, if any: the `tip` or `hint` option of a command (or
+/// subcommand), or the `tip` input of a modal.
+pub fn hint(interaction: &Interaction) -> Option<String> {
+    match interaction {
+        Interaction::Command(command) => {
+            let options = command.data.options();
+            let options = match options.first() {
+                Some(ResolvedOption {
+                    value: ResolvedValue::SubCommand(options),
+                    ..
+                }) => options,
+                _ => &options,
+            };
+            options.iter().find_map(|option| match option.value {
+                ResolvedValue::String(value) if matches!(option.name, "tip" | "hint") => {
+                    Some(value.to_string())
+                }
+                _ => None,
+            })
+        }
+        Interaction::Modal(modal) => input_text(&modal.data, "tip").map(String::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all};
+
+    // An audit log in a directory of its own, with the given number of entries:
+    fn log_with(name: &str, entries: usize) -> AuditConfig {
+        let dir = std::env::temp_dir().join(format!("mother-brain-audit-{name}"));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let config = AuditConfig {
+            file: dir.join("audit.log").to_string_lossy().into_owned(),
+            salt_file: dir.join("audit.salt").to_string_lossy().into_owned(),
+        };
+
+        let log = AuditLog::open(config.clone()).unwrap();
+        for i in 0..entries {
+            log.record(i as u64, "pswd", "ok", Some("hint"), None)
+                .unwrap();
+        }
+        config
+    }
+
+    #[test]
+    fn intact_log_verifies() {
+        let config = log_with("intact", 3);
+        assert_eq!(verify(&config).unwrap(), 3);
+
+        // Reopening goes on with the same chain:
+        let log = AuditLog::open(config.clone()).unwrap();
+        log.record(3, "cracktime", "denied", None, Some((None, 42)))
+            .unwrap();
+        assert_eq!(verify(&config).unwrap(), 4);
+    }
+
+    #[test]
+    fn new_log_verifies() {
+        let config = log_with("new", 0);
+        assert_eq!(verify(&config).unwrap(), 0);
+    }
+
+    #[test]
+    fn edited_entry_fails() {
+        let config = log_with("edited", 3);
+        let log = read_to_string(&config.file).unwrap();
+        write(
+            &config.file,
+            log.replacen("\"user_id\":1,", "\"user_id\":7,", 1),
+        )
+        .unwrap();
+        assert!(verify(&config).is_err());
+    }
+
+    #[test]
+    fn truncated_log_fails() {
+        let config = log_with("truncated", 3);
+        let log = read_to_string(&config.file).unwrap();
+        let kept: Vec<&str> = log.lines().take(2).collect();
+        write(&config.file, kept.join("\n") + "\n").unwrap();
+        assert!(verify(&config).is_err());
+    }
+
+    #[test]
+    fn missing_head_fails() {
+        let config = log_with("headless", 3);
+        std::fs::remove_file(config.head_file()).unwrap();
+        assert!(verify(&config).is_err());
+        assert!(AuditLog::open(config.clone()).is_err());
+    }
+
+    #[test]
+    fn entry_left_by_a_crash_is_accepted() {
+        // As if the bot crashed after appending the third entry, but before writing the head:
+        let config = log_with("crashed", 2);
+        let head = read(config.head_file()).unwrap();
+        AuditLog::open(config.clone())
+            .unwrap()
+            .record(2, "pswd", "ok", None, None)
+            .unwrap();
+        write(config.head_file(), head).unwrap();
+        assert!(verify(&config).is_err());
+
+        AuditLog::open(config.clone()).unwrap();
+        assert_eq!(verify(&config).unwrap(), 3);
+    }
+}
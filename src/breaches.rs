@@ -0,0 +1,347 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::config::BreachesConfig;
+use crate::error::{Error, Result};
+use crate::storage::write_atomically;
+use data_encoding::{HEXUPPER, HEXUPPER_PERMISSIVE};
+use openssl::sha::sha1;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+// Identifies Bloom filters built by the bot (followed by their size in bits, and the number of
+// hashes per entry):
+const BLOOM_MAGIC: &[u8; 8] = b"MBBLOOM1";
+const BLOOM_HEADER_LEN: u64 = 8 + 8 + 4;
+// Below this size, the sorted hash file is scanned instead of halved again:
+const SCAN_LEN: u64 = 4096;
+
+/// Whether a password appears in the breach corpus.
+pub enum Found {
+    /// It does, this many times (according to the sorted hash file).
+    Times(u64),
+    /// It most likely does (according to the Bloom filter, which has false positives).
+    Likely,
+    /// It does not.
+    No,
+}
+
+/// A local copy of a corpus of breached passwords, to check passwords against without any
+/// network call.
+pub enum Breaches {
+    /// Not configured.
+    None,
+    /// The SHA-1 hashes of the passwords, one per line, sorted, each followed by the number of
+    /// times it was seen (the format of Pwned Passwords: "<40 hex digits>:<count>").
+    Sorted(String),
+    /// A Bloom filter built from such a file, with `build-bloom`.
+    Bloom {
+        path: String,
+        bits: u64,
+        hashes: u32,
+    },
+}
+
+impl Breaches {
+    /// Opens the configured corpus. The sorted hash file is preferred, as it has no false
+    /// positives.
+    pub fn load(config: &BreachesConfig) -> Result<Self> {
+        if let Some(path) = &config.hashes_file {
+            File::open(path).map_err(Error::io(format!("Could not open breaches file {path}")))?;
+            return Ok(Self::Sorted(path.clone()));
+        }
+        let Some(path) = &config.bloom_file else {
+            return Ok(Self::None);
+        };
+
+        let mut header = [0; BLOOM_HEADER_LEN as usize];
+        File::open(path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .map_err(Error::io(format!("Could not read Bloom filter {path}")))?;
+        let bits = u64::from_le_bytes(header[8..16].try_into().unwrap_or_default());
+        let hashes = u32::from_le_bytes(header[16..20].try_into().unwrap_or_default());
+        if &header[..8] != BLOOM_MAGIC || bits == 0 || hashes == 0 {
+            return Err(Error::InvalidBreaches(format!(
+                "{path} is not a Bloom filter"
+            )));
+        }
+        Ok(Self::Bloom {
+            path: path.clone(),
+            bits,
+            hashes,
+        })
+    }
+
+    pub fn configured(&self) -> bool {
+        !matches!(self, Self::None)
+    }
+
+    /// Looks the password up in the corpus. Blocks on file reads.
+    pub fn check(&self, password: &str) -> Result<Found> {
+        let hash = sha1(password.as_bytes());
+        match self {
+            Self::None => Ok(Found::No),
+            Self::Sorted(path) => find_sorted(path, &HEXUPPER.encode(&hash)),
+            Self::Bloom { path, bits, hashes } => {
+                let mut file = File::open(path)
+                    .map_err(Error::io(format!("Could not open Bloom filter {path}")))?;
+                for bit in bloom_bits(&hash, *bits, *hashes) {
+                    let mut byte = [0];
+                    file.seek(SeekFrom::Start(BLOOM_HEADER_LEN + bit / 8))
+                        .and_then(|_| file.read_exact(&mut byte))
+                        .map_err(Error::io(format!("Could not read Bloom filter {path}")))?;
+                    if byte[0] & (1 << (bit % 8)) == 0 {
+                        return Ok(Found::No);
+                    }
+                }
+                Ok(Found::Likely)
+            }
+        }
+    }
+}
+
+// The bits set for a hash in a Bloom filter of the given size. SHA-1 hashes are uniform
+// already, so two halves of one make for the double hashing:
+fn bloom_bits(hash: &[u8; 20], bits: u64, hashes: u32) -> impl Iterator<Item = u64> {
+    let first = u64::from_le_bytes(hash[..8].try_into().unwrap_or_default());
+    let second = u64::from_le_bytes(hash[8..16].try_into().unwrap_or_default()) | 1;
+    (0..hashes as u64).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % bits)
+}
+
+// Reads the line starting at the current position, if any, as its hash and count.
+fn read_entry(reader: &mut impl BufRead, line: &mut String) -> Result<Option<(String, u64)>> {
+    line.clear();
+    if reader
+        .read_line(line)
+        .map_err(Error::io("Could not read the breaches file"))?
+        == 0
+    {
+        return Ok(None);
+    }
+    let (hash, count) = line
+        .trim_end()
+        .split_once(':')
+        .unwrap_or((line.trim_end(), "1"));
+    Ok(Some((hash.to_uppercase(), count.parse().unwrap_or(1))))
+}
+
+// Binary searches the sorted hash file, without reading it all (it's tens of gigabytes):
+fn find_sorted(path: &str, hash: &str) -> Result<Found> {
+    let file =
+        File::open(path).map_err(Error::io(format!("Could not open breaches file {path}")))?;
+    let len = file
+        .metadata()
+        .map_err(Error::io(format!("Could not open breaches file {path}")))?
+        .len();
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+
+    // Keep the hash between the first full lines after `low` and after `high`:
+    let (mut low, mut high) = (0, len);
+    let seek = |reader: &mut BufReader<File>, offset: u64| -> Result<()> {
+        reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(Error::io("Could not read the breaches file"))?;
+        // Skip the rest of the line the offset falls in:
+        if offset > 0 {
+            reader
+                .read_line(&mut String::new())
+                .map_err(Error::io("Could not read the breaches file"))?;
+        }
+        Ok(())
+    };
+    while high - low > SCAN_LEN {
+        let middle = low + (high - low) / 2;
+        seek(&mut reader, middle)?;
+        match read_entry(&mut reader, &mut line)? {
+            Some((entry, _)) if entry.as_str() < hash => low = middle,
+            _ => high = middle,
+        }
+    }
+
+    seek(&mut reader, low)?;
+    while let Some((entry, count)) = read_entry(&mut reader, &mut line)? {
+        match entry.as_str().cmp(hash) {
+            std::cmp::Ordering::Less => continue,
+            std::cmp::Ordering::Equal => return Ok(Found::Times(count)),
+            std::cmp::Ordering::Greater => break,
+        }
+    }
+    Ok(Found::No)
+}
+
+/// Builds the configured Bloom filter from the configured sorted hash file, sized for the
+/// configured false positive rate. Returns the number of hashes in it.
+pub fn build_bloom(config: &BreachesConfig) -> Result<u64> {
+    let (Some(hashes_file), Some(bloom_file)) = (&config.hashes_file, &config.bloom_file) else {
+        return Err(Error::InvalidBreaches(
+            "both hashes_file and bloom_file must be set".to_string(),
+        ));
+    };
+    let open = || {
+        File::open(hashes_file)
+            .map(BufReader::new)
+            .map_err(Error::io(format!(
+                "Could not open breaches file {hashes_file}"
+            )))
+    };
+
+    // Size the filter for the number of hashes (the optimal size and number of hash functions):
+    let mut entries = 0;
+    let mut line = String::new();
+    let mut reader = open()?;
+    while read_entry(&mut reader, &mut line)?.is_some() {
+        entries += 1;
+    }
+    let rate = config.false_positive_rate.clamp(1e-9, 0.5);
+    let ln2 = 2f64.ln();
+    let bits = ((-(entries.max(1) as f64) * rate.ln() / (ln2 * ln2)).ceil() as u64).max(8);
+    let hashes = ((bits as f64 / entries.max(1) as f64 * ln2).round() as u32).max(1);
+
+    let mut filter = vec![0u8; bits.div_ceil(8) as usize];
+    let mut reader = open()?;
+    while let Some((entry, _)) = read_entry(&mut reader, &mut line)? {
+        let hash: [u8; 20] = HEXUPPER_PERMISSIVE
+            .decode(entry.as_bytes())
+            .ok()
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| Error::InvalidBreaches(format!("{entry} is not a SHA-1 hash")))?;
+        for bit in bloom_bits(&hash, bits, hashes) {
+            filter[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+
+    write_atomically(bloom_file, "Could not write the Bloom filter", |file| {
+        file.write_all(BLOOM_MAGIC)?;
+        file.write_all(&bits.to_le_bytes())?;
+        file.write_all(&hashes.to_le_bytes())?;
+        file.write_all(&filter)
+    })?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    // Passwords in the test corpus, each seen as many times as its position (plus one):
+    fn passwords() -> Vec<String> {
+        (0..5000).map(|i| format!("password{i}")).collect()
+    }
+
+    // A corpus in the format of Pwned Passwords (sorted, with CRLF line endings), big enough
+    // for the lookups to halve it several times:
+    fn corpus(name: &str) -> BreachesConfig {
+        let dir = std::env::temp_dir().join(format!("mother-brain-breaches-{name}"));
+        create_dir_all(&dir).unwrap();
+        let mut lines: Vec<String> = (passwords().iter().enumerate())
+            .map(|(i, password)| {
+                format!(
+                    "{}:{}\r\n",
+                    HEXUPPER.encode(&sha1(password.as_bytes())),
+                    i + 1
+                )
+            })
+            .collect();
+        lines.sort();
+        let hashes_file = dir.join("hashes.txt");
+        write(&hashes_file, lines.concat()).unwrap();
+        BreachesConfig {
+            hashes_file: Some(hashes_file.to_string_lossy().into_owned()),
+            bloom_file: Some(dir.join("hashes.bloom").to_string_lossy().into_owned()),
+            false_positive_rate: 0.001,
+        }
+    }
+
+    #[test]
+    fn sorted_finds_every_hash() {
+        let config = corpus("sorted");
+        let breaches = Breaches::load(&config).unwrap();
+        for (i, password) in passwords().iter().enumerate() {
+            assert!(matches!(
+                breaches.check(password).unwrap(),
+                Found::Times(count) if count == i as u64 + 1
+            ));
+        }
+    }
+
+    #[test]
+    fn sorted_finds_first_and_last_lines() {
+        let config = corpus("edges");
+        let path = config.hashes_file.unwrap();
+        let hashes = std::fs::read_to_string(&path).unwrap();
+        for line in [hashes.lines().next(), hashes.lines().last()] {
+            let (hash, count) = line.unwrap().split_once(':').unwrap();
+            assert!(matches!(
+                find_sorted(&path, hash).unwrap(),
+                Found::Times(found) if found.to_string() == count
+            ));
+        }
+        assert!(matches!(
+            find_sorted(&path, &"0".repeat(40)).unwrap(),
+            Found::No
+        ));
+        assert!(matches!(
+            find_sorted(&path, &"F".repeat(40)).unwrap(),
+            Found::No
+        ));
+    }
+
+    #[test]
+    fn sorted_misses_other_passwords() {
+        let breaches = Breaches::load(&corpus("misses")).unwrap();
+        for i in 0..1000 {
+            assert!(matches!(
+                breaches.check(&format!("not breached {i}")).unwrap(),
+                Found::No
+            ));
+        }
+    }
+
+    #[test]
+    fn bloom_bits_are_within_the_filter() {
+        let hash = sha1(b"password");
+        let bits: Vec<u64> = bloom_bits(&hash, 1000, 7).collect();
+        assert_eq!(bits.len(), 7);
+        assert!(bits.iter().all(|&bit| bit < 1000));
+        assert_eq!(bits, bloom_bits(&hash, 1000, 7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn bloom_finds_every_hash_with_few_false_positives() {
+        let mut config = corpus("bloom");
+        assert_eq!(build_bloom(&config).unwrap(), passwords().len() as u64);
+        config.hashes_file = None;
+        let breaches = Breaches::load(&config).unwrap();
+        for password in passwords() {
+            assert!(matches!(breaches.check(&password).unwrap(), Found::Likely));
+        }
+
+        // 0.1% expected, so 10 of 10000 (allowing for bad luck):
+        let false_positives = (0..10000)
+            .filter(|i| {
+                let found = breaches.check(&format!("not breached {i}")).unwrap();
+                matches!(found, Found::Likely)
+            })
+            .count();
+        assert!(false_positives < 50, "{false_positives} false positives");
+    }
+}
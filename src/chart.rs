@@ -0,0 +1,134 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::duration::{DAY, HOUR, YEAR};
+use crate::error::{Error, Result};
+use plotters::prelude::*;
+use std::{fmt::Display, sync::Once};
+
+// Charts are drawn with a bundled font, so they look the same everywhere (see its license in
+// the same directory):
+const FONT: &[u8] = include_bytes!("../data/fonts/DejaVuSans.ttf");
+const WIDTH: u32 = 960;
+const HEIGHT: u32 = 600;
+const UNITS: [(&str, f64); 5] = [
+    ("s", 1.0),
+    ("min", 60.0),
+    ("h", HOUR),
+    ("d", DAY),
+    ("y", YEAR),
+];
+
+/// A line of a chart: crack times (in seconds) by strength.
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(u32, f64)>,
+}
+
+fn failed(err: impl Display) -> Error {
+    Error::Chart(err.to_string())
+}
+
+// Labels a time on the axis, in the largest unit that fits it (e.g. "2.5 h" or "1e12 y"):
+fn short_time(seconds: f64) -> String {
+    let (unit, length) = UNITS
+        .iter()
+        .rev()
+        .find(|(_, length)| seconds >= *length)
+        .copied()
+        .unwrap_or(UNITS[0]);
+    let value = seconds / length;
+    if !(0.1..1e3).contains(&value) {
+        format!("{value:.0e} {unit}")
+    } else if value >= 10.0 {
+        format!("{value:.0} {unit}")
+    } else {
+        format!("{value:.1} {unit}").replacen(".0 ", " ", 1)
+    }
+}
+
+/// Draws the crack times of every series against strength, on a logarithmic scale, as a PNG.
+pub fn crack_times(title: &str, series: &[Series]) -> Result<Vec<u8>> {
+    static FONT_REGISTERED: Once = Once::new();
+    FONT_REGISTERED.call_once(|| {
+        // An invalid font would only leave the labels out:
+        let _ = plotters::style::register_font("sans-serif", FontStyle::Normal, FONT);
+    });
+
+    // Fit the axes to the points, with whole powers of ten for the times:
+    let points = || {
+        (series.iter().flat_map(|series| &series.points))
+            .filter(|(_, seconds)| seconds.is_finite() && *seconds > 0.0)
+    };
+    let strengths = points().map(|(strength, _)| *strength);
+    let (min_x, max_x) = (strengths.clone().min(), strengths.max());
+    let (Some(min_x), Some(max_x)) = (min_x, max_x) else {
+        return Err(Error::Chart("there is nothing to draw".to_string()));
+    };
+    let times = || points().map(|(_, seconds)| seconds.log10());
+    let min_y = 10f64.powf(times().fold(f64::INFINITY, f64::min).floor());
+    let max_y = 10f64.powf(times().fold(f64::NEG_INFINITY, f64::max).ceil() + 1.0);
+
+    let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).map_err(failed)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 24))
+            .margin(16)
+            .x_label_area_size(48)
+            .y_label_area_size(80)
+            .build_cartesian_2d(min_x..max_x.max(min_x + 1), (min_y..max_y).log_scale())
+            .map_err(failed)?;
+        chart
+            .configure_mesh()
+            .x_desc("Strength (words)")
+            .y_desc("Average time to crack")
+            .y_label_formatter(&|seconds| short_time(*seconds))
+            .draw()
+            .map_err(failed)?;
+
+        for (i, series) in series.iter().enumerate() {
+            let color = Palette99::pick(i).stroke_width(2);
+            let points = (series.points.iter().copied())
+                .filter(|(_, seconds)| seconds.is_finite() && *seconds > 0.0);
+            chart
+                .draw_series(LineSeries::new(points, color))
+                .map_err(failed)?
+                .label(&series.label)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .map_err(failed)?;
+        root.present().map_err(failed)?;
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(failed)?;
+    Ok(png)
+}
@@ -0,0 +1,99 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{input_text, Command, Response};
+use crate::breaches::Found;
+use crate::error::{Error, Result};
+use crate::state::State;
+use serenity::builder::{CreateActionRow, CreateCommand, CreateInputText, CreateModal};
+use serenity::model::application::{
+    CommandInteraction, InputTextStyle, ModalInteraction, ModalInteractionData,
+};
+use serenity::prelude::Context;
+use zeroize::Zeroizing;
+
+fn check(modal: &ModalInteractionData, state: &State) -> Result<Response> {
+    let password = Zeroizing::new(
+        input_text(modal, "password")
+            .ok_or(Error::InvalidOptions(
+                "breached modal's password is missing",
+            ))?
+            .to_string(),
+    );
+    let breaches = state.breaches.clone();
+    Ok(Response::blocking(state, move || {
+        Ok(match breaches.check(&password)? {
+            Found::Times(1) => {
+                "That password appears once in known breaches. Don't use it.".to_string()
+            }
+            Found::Times(times) => {
+                format!("That password appears {times} times in known breaches. Don't use it.")
+            }
+            Found::Likely => {
+                "That password most likely appears in known breaches. Don't use it.".to_string()
+            }
+            Found::No => "That password doesn't appear in known breaches.".to_string(),
+        })
+    }))
+}
+
+pub struct Breached;
+
+#[serenity::async_trait]
+impl Command for Breached {
+    fn name(&self) -> &'static str {
+        "breached"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name()).description(
+            "Check whether a password appears in known breaches (without going online).",
+        )
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        _command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        if !state.breaches.configured() {
+            return Ok(Response::text("I have no breach corpus to check against."));
+        }
+
+        // Ask for the password through a modal, so it doesn't stay in the input box or in the
+        // command history (nor in the audit log, which records options named like hints):
+        Ok(Response::Modal(
+            CreateModal::new(self.name(), "Check for breaches").components(vec![
+                CreateActionRow::InputText(
+                    CreateInputText::new(InputTextStyle::Short, "Password", "password")
+                        .placeholder("It's never logged nor kept."),
+                ),
+            ]),
+        ))
+    }
+
+    async fn submit(
+        &self,
+        _ctx: &Context,
+        modal: &ModalInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        check(&modal.data, state)
+    }
+}
@@ -16,12 +16,33 @@
  *  You should have received a copy of the GNU Affero General Public License
  *  along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
+use super::{Command, Response};
+use crate::error::Result;
+use crate::state::State;
 use serenity::builder::CreateCommand;
+use serenity::model::application::CommandInteraction;
+use serenity::prelude::Context;
 
-pub fn run() -> String {
-    "My source code can be found here: https://github.com/0xb01u/mother-brain".to_string()
-}
+pub struct Code;
+
+#[serenity::async_trait]
+impl Command for Code {
+    fn name(&self) -> &'static str {
+        "code"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name()).description("Get a link to this bot's source code.")
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("code").description("Get a link to this bot's source code.")
+    async fn run(
+        &self,
+        _ctx: &Context,
+        _command: &CommandInteraction,
+        _state: &State,
+    ) -> Result<Response> {
+        Ok(Response::text(
+            "My source code can be found here: https://github.com/0xb01u/mother-brain",
+        ))
+    }
 }
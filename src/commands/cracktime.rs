@@ -20,48 +20,256 @@ extern crate rand;
 extern crate rand_chacha;
 extern crate rs_sha512;
 
-use serenity::builder::{CreateCommand, CreateCommandOption};
-use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use super::{
+    boolean_option, integer_option, push_lines, string_option, Command, Response, MAX_RESPONSE_LEN,
+};
+use crate::attackers::{Costs, Models, Setup};
+use crate::chart::{self, Series};
+use crate::commands::pswd::WORDLIST;
+use crate::duration::{self, DAY, HOUR, YEAR};
+use crate::error::{Error, Result};
+use crate::fuzzy;
+use crate::state::State;
+use serenity::builder::{
+    CreateAttachment, CreateAutocompleteResponse, CreateCommand, CreateCommandOption, CreateEmbed,
+    CreateInteractionResponseMessage, EditInteractionResponse,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::model::Timestamp;
+use serenity::prelude::Context;
 use std::fs::read_to_string;
 
-// Some interesting pages:
-//  * https://pmdn.org/password-strength/
-//  * https://alecmccutcheon.github.io/Password-Entropy-Calculator/
-const TWO_BILLION: f64 = 2e9;
-const ONE_TRILLION: f64 = 1e12;
-const POWERFUL_MINER: f64 = 390e12; // MicroBT Whatsminer MS Hydro90T ($13,699).
-const MILLION_DOLLAR_MINER: f64 = 132.0 * 335e12; // Bitmain Antminer S21 Hydro ($7,599).
-const SHA256_TO_BCRYPT5_TIME: f64 = 8.81e6 / (1.0 / (0.0024385929107666 / 4.0));
-// Sources for the previous estimation ^^^:
-//  * https://wildlyinaccurate.com/bcrypt-choosing-a-work-factor/ (Intel i3-2120 (Quad Core, 3.30GHz))
-//    (assumed only one core used).
-//  * https://en.bitcoin.it/wiki/Non-specialized_hardware_comparison#CPUs.2FAPUs (Core i3-2100
-//    @ 3.10 GHz, extrapolated to0 GHz).
-const HOURS_TO_SECONDS: f64 = 3600.0;
-const DAYS_TO_SECONDS: f64 = 3600.0 * 24.0;
-const YEARS_TO_SECONDS: f64 = 3600.0 * 24.0 * 365.0;
-
-pub fn run(options: &[ResolvedOption]) -> String {
-    let mut response = "".to_owned();
+/// Hash parameters that can be set through options of the same name, with the options'
+/// descriptions and bounds. Parameters in the attacker models must be one of these.
+pub const PARAMETERS: [(&str, &str, u64, Option<u64>); 5] = [
+    ("cost", "bcrypt's cost (work factor). Default is 5.", 4, Some(31)),
+    ("iterations", "PBKDF2's iterations. Default is 999.", 1, None),
+    (
+        "memory_kib",
+        "Memory used by scrypt or Argon2id, in KiB. Default is 16384 for scrypt, and 65536 for Argon2id.",
+        1,
+        None,
+    ),
+    ("parallelism", "scrypt's parallelization (p). Default is 1.", 1, None),
+    ("time", "Argon2id's passes (t). Default is 3.", 1, None),
+];
+
+// Charts go at least up to this strength, for comparison:
+const CHART_STRENGTHS: i32 = 10;
+// Discord refuses embed descriptions longer than 4096 characters:
+const MAX_EMBED_LEN: usize = 4000;
+
+// The crack times estimated, and what they are based on.
+struct Estimate {
+    text: String,
+    sources: Vec<String>,
+    series: Vec<Series>,
+}
+
+// An estimate split into what Discord accepts: the sources go in an embed, and a text too
+// long for a message is cut at a line, with the whole of it attached as a file.
+struct Reply {
+    content: String,
+    full: Option<CreateAttachment>,
+    sources: Option<CreateEmbed>,
+}
+
+impl Reply {
+    fn new(text: String, sources: &[String]) -> Self {
+        let mut full = None;
+        let mut content = text;
+        if content.len() > MAX_RESPONSE_LEN {
+            let lines: Vec<String> = content.lines().map(|line| format!("{line}\n")).collect();
+            let mut cut = String::new();
+            push_lines(&mut cut, lines.into_iter());
+            cut.push_str("The whole estimate is in the attached file.");
+            full = Some(CreateAttachment::bytes(
+                content.into_bytes(),
+                "cracktime.md",
+            ));
+            content = cut;
+        }
+
+        let mut links = String::new();
+        for source in sources {
+            if links.len() + source.len() + 1 > MAX_EMBED_LEN {
+                break;
+            }
+            links.push_str(source);
+            links.push('\n');
+        }
+        let sources =
+            (!links.is_empty()).then(|| CreateEmbed::new().title("Sources").description(links));
+
+        Self {
+            content,
+            full,
+            sources,
+        }
+    }
+
+    fn message(self) -> CreateInteractionResponseMessage {
+        let mut message = CreateInteractionResponseMessage::new()
+            .content(self.content)
+            .ephemeral(true);
+        if let Some(sources) = self.sources {
+            message = message.embed(sources);
+        }
+        if let Some(full) = self.full {
+            message = message.add_file(full);
+        }
+        message
+    }
+
+    fn edit(self) -> EditInteractionResponse {
+        let mut edit = EditInteractionResponse::new().content(self.content);
+        if let Some(sources) = self.sources {
+            edit = edit.embed(sources);
+        }
+        if let Some(full) = self.full {
+            edit = edit.new_attachment(full);
+        }
+        edit
+    }
+}
+
+/// Describes how long cracking takes, in the most meaningful unit or in scientific notation.
+pub fn describe_time(seconds: f64, scientific: bool) -> String {
+    if scientific {
+        return format!(
+            "{:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years",
+            seconds,
+            seconds / HOUR,
+            seconds / DAY,
+            seconds / YEAR
+        );
+    }
+    match duration::compare(seconds) {
+        Some(comparison) => format!("{} ({comparison})", duration::humanize(seconds)),
+        None => duration::humanize(seconds),
+    }
+}
 
-    // Get the number of words (strength) used for the password:
-    let num_words: i32;
-    if let Some(ResolvedOption {
-        value: ResolvedValue::Integer(nw),
-        ..
-    }) = options.first()
-    {
-        num_words = *nw as i32;
+// Rounds to two significant digits for small values, in the largest of the given units:
+fn scaled(value: f64, units: &[(&str, f64)]) -> String {
+    let (unit, size) = units
+        .iter()
+        .rev()
+        .find(|(_, size)| value >= *size)
+        .copied()
+        .unwrap_or(units[0]);
+    let value = value / size;
+    if value >= 1000.0 {
+        format!("{value:.1e}{unit}")
+    } else if value < 10.0 {
+        format!("{value:.1}{unit}").replacen(".0", "", 1)
     } else {
-        // Default to 6 words for the password:
-        num_words = 6;
+        format!("{value:.0}{unit}")
     }
+}
+
+fn describe_money(dollars: f64) -> String {
+    if dollars < 1.0 {
+        return "less than $1".to_string();
+    }
+    if dollars < 1e6 {
+        // Whole dollars, with thousands separators:
+        let digits = format!("{dollars:.0}");
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        return format!("${grouped}");
+    }
+    let units = [
+        (" million", 1e6),
+        (" billion", 1e9),
+        (" trillion", 1e12),
+        (" quadrillion", 1e15),
+    ];
+    format!("${}", scaled(dollars, &units))
+}
+
+fn describe_energy(kwh: f64) -> String {
+    let units = [
+        (" kWh", 1.0),
+        (" MWh", 1e3),
+        (" GWh", 1e6),
+        (" TWh", 1e9),
+        (" PWh", 1e12),
+    ];
+    scaled(kwh, &units)
+}
+
+// Lists what the costs of cracking within some time are known, if any:
+fn describe_costs(costs: &Costs) -> Option<String> {
+    let copies = match costs.copies {
+        copies if copies < 1e6 => format!("{copies}"),
+        copies => format!("{copies:.1e}"),
+    };
+    let mut parts = Vec::new();
+    if let Some(capital) = costs.capital {
+        parts.push(format!("buying {copies} ({})", describe_money(capital)));
+    }
+    if let (Some(kwh), Some(price)) = (costs.energy_kwh, costs.electricity) {
+        parts.push(format!(
+            "plus {} of electricity ({})",
+            describe_energy(kwh),
+            describe_money(price)
+        ));
+    }
+    if let Some(rental) = costs.rental {
+        let or = if parts.is_empty() { "" } else { "or " };
+        parts.push(format!("{or}renting {copies} ({})", describe_money(rental)));
+    }
+    match parts.len() {
+        0 => None,
+        _ => Some(parts.join(", ")),
+    }
+}
+
+// Estimates the crack times, also giving them by strength for the chart.
+fn estimate(options: &[ResolvedOption], models: &Models) -> Result<Estimate> {
+    let mut response = "".to_owned();
+    let mut series = Vec::new();
+
+    // Get the number of words (strength) used for the password (default to 6):
+    let num_words = integer_option(options, "strength").unwrap_or(6) as i32;
+    let scientific = boolean_option(options, "scientific").unwrap_or(false);
+    let within_days = integer_option(options, "within_days");
+    let years_ahead = integer_option(options, "years_ahead");
+    let online = string_option(options, "attack") == Some("online");
+
+    // Set up the chosen hash (or the default ones) with the given parameters. Online, hashes
+    // don't matter:
+    let values = parameter_values(options);
+    let hashes: Vec<Setup> = match string_option(options, "hash") {
+        _ if online => Vec::new(),
+        Some(id) => match models.hash(id) {
+            Some(hash) => vec![hash.with(&values)],
+            None => {
+                return Ok(Estimate {
+                    text: format!("I don't know the hash `{id}`."),
+                    sources: Vec::new(),
+                    series,
+                })
+            }
+        },
+        None => models
+            .hashes
+            .iter()
+            .filter(|hash| hash.default)
+            .map(|hash| hash.with(&values))
+            .collect(),
+    };
 
     // Load list of words to compose the password:
-    let dict_size = read_to_string("wordlist.txt")
-        .expect("Could not open word-list file.")
-        .lines()
-        .count() as f64;
+    let dict_size = dictionary_size()?;
 
     // Compute number of possible passwords:
     let num_options = dict_size.powi(num_words);
@@ -69,7 +277,7 @@ pub fn run(options: &[ResolvedOption]) -> String {
     // Average attempts to find the password:
     let avg_crack_attempts = num_options / 2.0;
 
-    // Estimate crack time:
+    // Estimate crack time, for every hash and attacker hardware:
     response.push_str(&format!(
         "Number of possible options/combinations: {:.3e}\n",
         num_options
@@ -78,59 +286,257 @@ pub fn run(options: &[ResolvedOption]) -> String {
         "Password entropy: {:.3} bits\n",
         num_options.log(2.0)
     ));
-    response.push_str("\n**Assuming SHA-256 hashes:**\n");
-    let guesses_two_billion_gps = avg_crack_attempts / TWO_BILLION;
-    // https://www.password-depot.de/en/know-how/brute-force-attacks.htm VVV
-    response.push_str(&format!("With 2 billion guesses/s (\"a very strong single computer\" in 2012): {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-                              guesses_two_billion_gps, guesses_two_billion_gps / HOURS_TO_SECONDS,
-                              guesses_two_billion_gps / DAYS_TO_SECONDS, guesses_two_billion_gps / YEARS_TO_SECONDS));
-    let guesses_one_trillion_gps = avg_crack_attempts / ONE_TRILLION;
-    response.push_str(&format!(
-        "With 1 trillion guesses/s: {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-        guesses_one_trillion_gps,
-        guesses_one_trillion_gps / HOURS_TO_SECONDS,
-        guesses_one_trillion_gps / DAYS_TO_SECONDS,
-        guesses_one_trillion_gps / YEARS_TO_SECONDS
-    ));
-    let guesses_powerful_miner_gps = avg_crack_attempts / POWERFUL_MINER;
-    response.push_str(&format!("With the equivalent of a powerful bitcoin miner: {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-                              guesses_powerful_miner_gps, guesses_powerful_miner_gps / HOURS_TO_SECONDS,
-                              guesses_powerful_miner_gps / DAYS_TO_SECONDS, guesses_powerful_miner_gps / YEARS_TO_SECONDS));
-    let guesses_million_dollar_miner_gps = avg_crack_attempts / MILLION_DOLLAR_MINER;
-    response.push_str(&format!("With the equivalent of $1M worth of bitcoin miners (cost efficient): {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-                              guesses_million_dollar_miner_gps, guesses_million_dollar_miner_gps / HOURS_TO_SECONDS,
-                              guesses_million_dollar_miner_gps / DAYS_TO_SECONDS, guesses_million_dollar_miner_gps / YEARS_TO_SECONDS));
-    response.push_str("\n**Assuming Bcypt hashes of work factor 5 (the minimum recommended):**\n");
-    let guesses_two_billion_gps = avg_crack_attempts / (TWO_BILLION / SHA256_TO_BCRYPT5_TIME);
-    response.push_str(&format!("With 2 billion guesses/s (\"a very strong single computer\" in 2012): {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-                              guesses_two_billion_gps, guesses_two_billion_gps / HOURS_TO_SECONDS,
-                              guesses_two_billion_gps / DAYS_TO_SECONDS, guesses_two_billion_gps / YEARS_TO_SECONDS));
-    let guesses_one_trillion_gps = avg_crack_attempts / (ONE_TRILLION / SHA256_TO_BCRYPT5_TIME);
-    response.push_str(&format!(
-        "With 1 trillion guesses/s: {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-        guesses_one_trillion_gps,
-        guesses_one_trillion_gps / HOURS_TO_SECONDS,
-        guesses_one_trillion_gps / DAYS_TO_SECONDS,
-        guesses_one_trillion_gps / YEARS_TO_SECONDS
-    ));
-    let guesses_powerful_miner_gps = avg_crack_attempts / (POWERFUL_MINER / SHA256_TO_BCRYPT5_TIME);
-    response.push_str(&format!("With the equivalent of a powerful bitcoin miner: {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-                              guesses_powerful_miner_gps, guesses_powerful_miner_gps / HOURS_TO_SECONDS,
-                              guesses_powerful_miner_gps / DAYS_TO_SECONDS, guesses_powerful_miner_gps / YEARS_TO_SECONDS));
-    let guesses_million_dollar_miner_gps =
-        avg_crack_attempts / (MILLION_DOLLAR_MINER / SHA256_TO_BCRYPT5_TIME);
-    response.push_str(&format!("With the equivalent of $1M worth of bitcoin miners (cost efficient): {:.3e} seconds, {:.3e} hours, {:.3e} days, {:.3e} years.\n",
-                              guesses_million_dollar_miner_gps, guesses_million_dollar_miner_gps / HOURS_TO_SECONDS,
-                              guesses_million_dollar_miner_gps / DAYS_TO_SECONDS, guesses_million_dollar_miner_gps / YEARS_TO_SECONDS));
+    let this_year = Timestamp::now().year();
+    if let (Some(years), false) = (years_ahead, online) {
+        response.push_str(&format!(
+            "Projecting an attack that starts in {}, with hardware getting twice as fast every {} years.\n",
+            this_year + years as i32,
+            models.doubling_years
+        ));
+    }
+
+    // Average crack times for every strength, given the time to make some guesses:
+    let curve = |time: &dyn Fn(f64) -> f64| -> Vec<(u32, f64)> {
+        (1..=num_words.max(CHART_STRENGTHS) as u32)
+            .map(|strength| (strength, time(dict_size.powi(strength as i32) / 2.0)))
+            .collect()
+    };
+
+    // Online, guesses go as fast as the service allows (however fast the attacker's hardware):
+    if online {
+        response.push_str("\n**Online attacks:**\n");
+        for online in &models.online {
+            let seconds = avg_crack_attempts / online.rate;
+            response.push_str(&format!(
+                "Through {}: {}.\n",
+                online.description,
+                describe_time(seconds, scientific)
+            ));
+            series.push(Series {
+                label: online.id.clone(),
+                points: curve(&|attempts| attempts / online.rate),
+            });
+        }
+    }
+    for hash in &hashes {
+        response.push_str(&format!("\n**Assuming {} hashes:**\n", hash.name()));
+        for hardware in &models.hardware {
+            let rate = hardware.guess_rate(hash);
+            let seconds = avg_crack_attempts / rate;
+            series.push(Series {
+                label: match hashes.len() {
+                    1 => hardware.id.clone(),
+                    _ => format!("{}, {}", hardware.id, hash.name()),
+                },
+                points: curve(&|attempts| match years_ahead {
+                    Some(years) => models.projected_time(attempts, rate, years as f64),
+                    None => attempts / rate,
+                }),
+            });
+            let date = match &hardware.date {
+                Some(date) => format!(" (as of {date})"),
+                None => String::new(),
+            };
+            match years_ahead {
+                Some(years) => {
+                    let projected = models.projected_time(avg_crack_attempts, rate, years as f64);
+                    // Only give the year while it's a believable one:
+                    let end = years as f64 + projected / YEAR;
+                    let year = if end < 1e6 {
+                        format!(", cracked around {}", this_year + end as i32)
+                    } else {
+                        String::new()
+                    };
+                    response.push_str(&format!(
+                        "With {}{date}: {}{year}.\n",
+                        hardware.description,
+                        describe_time(projected, scientific)
+                    ));
+                }
+                None => response.push_str(&format!(
+                    "With {}{date}: {}.\n",
+                    hardware.description,
+                    describe_time(seconds, scientific)
+                )),
+            }
+
+            // What cracking it within the target time would cost (with today's hardware), when
+            // known:
+            let Some(days) = within_days else { continue };
+            let within = days as f64 * DAY;
+            let costs = hardware.costs(seconds, within, models.price_per_kwh);
+            if let Some(costs) = describe_costs(&costs) {
+                response.push_str(&format!("-# Within {days} days: {costs}.\n"));
+            }
+        }
+    }
 
+    Ok(Estimate {
+        text: response,
+        sources: models.sources().into_iter().map(str::to_string).collect(),
+        series,
+    })
+}
+
+/// Suggests the known hashes for the `hash` option, and the known hardware for the `hardware`
+/// option, fuzzily matched by name.
+/// Adds an option for each of the hash parameters to the command.
+pub fn add_parameter_options(mut command: CreateCommand) -> CreateCommand {
+    for (name, description, min, max) in PARAMETERS {
+        let mut option = CreateCommandOption::new(CommandOptionType::Integer, name, description)
+            .min_int_value(min)
+            .required(false);
+        if let Some(max) = max {
+            option = option.max_int_value(max);
+        }
+        command = command.add_option(option);
+    }
+    command
+}
+
+/// Returns the hash parameters set in the options, by name.
+pub fn parameter_values(options: &[ResolvedOption]) -> Vec<(&'static str, f64)> {
+    PARAMETERS
+        .iter()
+        .filter_map(|&(name, ..)| Some((name, integer_option(options, name)? as f64)))
+        .collect()
+}
+
+pub fn complete_models(options: &[ResolvedOption], models: &Models) -> CreateAutocompleteResponse {
+    let Some((option, typed)) = options.iter().find_map(|option| match option.value {
+        ResolvedValue::Autocomplete { value, .. } => Some((option.name, value)),
+        _ => None,
+    }) else {
+        return CreateAutocompleteResponse::new();
+    };
+    let choices: Vec<(&str, &str)> = match option {
+        "hash" => (models.hashes.iter())
+            .map(|hash| (hash.name.as_str(), hash.id.as_str()))
+            .collect(),
+        "hardware" => (models.hardware.iter())
+            .map(|hardware| (hardware.description.as_str(), hardware.id.as_str()))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // Discord allows at most 25 suggestions:
+    let names = choices.iter().map(|(name, _)| *name);
+    let mut response = CreateAutocompleteResponse::new();
+    for name in fuzzy::matches(typed, names).into_iter().take(25) {
+        if let Some((_, id)) = choices.iter().find(|(choice, _)| *choice == name) {
+            response = response.add_string_choice(name, *id);
+        }
+    }
     response
 }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("cracktime").description("Give an estimate for the average time needed to crack a generated password.")
-        .add_option(
-            CreateCommandOption::new(CommandOptionType::Integer, "strength", "Strength of the password to crack. Valid values are between 1 and 50 (included). Default is 6.")
-                .min_int_value(1).max_int_value(50)
-                .required(false)
-        )
+/// The number of words passwords can be made of.
+pub fn dictionary_size() -> Result<f64> {
+    Ok(read_to_string(WORDLIST)
+        .map_err(Error::io("Could not open word-list file"))?
+        .lines()
+        .count() as f64)
+}
+
+pub struct Cracktime;
+
+#[serenity::async_trait]
+impl Command for Cracktime {
+    fn name(&self) -> &'static str {
+        "cracktime"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let command = CreateCommand::new(self.name()).description("Give an estimate for the average time needed to crack a generated password.")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "strength", "Strength of the password to crack. Valid values are between 1 and 50 (included). Default is 6.")
+                    .min_int_value(1).max_int_value(50)
+                    .required(false)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "hash", "Hash algorithm protecting the password. Default is SHA-256 and bcrypt.")
+                    .set_autocomplete(true)
+                    .required(false))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "attack", "Whether to crack stolen hashes (offline) or guess through a live service (online). Default is offline.")
+                    .add_string_choice("offline", "offline")
+                    .add_string_choice("online", "online")
+                    .required(false))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "chart", "Also draw a chart of the crack times by strength. Default is false.")
+                    .required(false))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "scientific", "Give times in scientific notation, in several units. Default is false.")
+                    .required(false))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "years_ahead", "Project the attack as starting this many years from now, on ever faster hardware.")
+                    .min_int_value(0).max_int_value(100)
+                    .required(false))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "within_days", "Also estimate what cracking the password within this many days would cost.")
+                    .min_int_value(1)
+                    .required(false));
+        add_parameter_options(command)
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        let options = command.data.options();
+        let Estimate {
+            text,
+            sources,
+            series,
+        } = estimate(&options, &state.models)?;
+        let reply = Reply::new(text, &sources);
+        if !boolean_option(&options, "chart").unwrap_or(false) || series.is_empty() {
+            return Ok(Response::Message(reply.message()));
+        }
+
+        // Drawing takes a while, so it's done in the blocking pool:
+        let pool = state.pool.clone();
+        Ok(Response::Deferred(Box::pin(async move {
+            let png = pool
+                .run(move || chart::crack_times("Crack times by strength", &series))
+                .await?;
+            Ok(reply
+                .edit()
+                .new_attachment(CreateAttachment::bytes(png, "cracktime.png")))
+        })))
+    }
+
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        autocomplete: &CommandInteraction,
+        state: &State,
+    ) -> Result<CreateAutocompleteResponse> {
+        Ok(complete_models(&autocomplete.data.options(), &state.models))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_estimates_are_sent_whole() {
+        let reply = Reply::new("Password entropy: 77.549 bits\n".to_string(), &[]);
+        assert_eq!(reply.content, "Password entropy: 77.549 bits\n");
+        assert!(reply.full.is_none());
+        assert!(reply.sources.is_none());
+    }
+
+    #[test]
+    fn long_estimates_are_cut_and_attached() {
+        let line = "With a rig of 8 RTX 4090 (as of 2023): about 4.2 thousand years.\n";
+        let reply = Reply::new(line.repeat(100), &["https://example.com".to_string()]);
+        assert!(reply.content.chars().count() <= 2000);
+        assert!(reply.content.starts_with(line));
+        assert!(reply.full.is_some());
+        assert!(reply.sources.is_some());
+    }
 }
@@ -0,0 +1,237 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{integer_option, push_lines, string_option, truncate, Command, Response};
+use crate::error::{Error, Result};
+use crate::fuzzy;
+use crate::state::State;
+use crate::vault::{self, Entry};
+use serenity::builder::{CreateAutocompleteResponse, CreateCommand, CreateCommandOption};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::prelude::Context;
+
+fn describe(entry: &Entry) -> String {
+    format!(
+        "`{}` (strength {}, word list {}, counter {})",
+        entry.hint, entry.strength, entry.wordlist, entry.counter
+    )
+}
+
+fn manage(options: &[ResolvedOption], user_id: u64, pswd_key: &[u8]) -> Result<String> {
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        return Err(Error::InvalidOptions(
+            "hints command was invoked without a subcommand",
+        ));
+    };
+
+    Ok(match *subcommand {
+        "list" => {
+            let entries = vault::load(pswd_key, user_id)?;
+            if entries.is_empty() {
+                return Ok("Your hint vault is empty.".to_string());
+            }
+
+            let mut response = format!("Your hint vault has {} hint(s):\n", entries.len());
+            let lines = entries
+                .iter()
+                .map(|entry| format!("- {}\n", describe(entry)));
+            push_lines(&mut response, lines);
+            response
+        }
+        "add" => {
+            let entry = Entry {
+                hint: string_option(options, "hint")
+                    .unwrap_or_default()
+                    .to_string(),
+                strength: integer_option(options, "strength").unwrap_or(6) as u32,
+                wordlist: string_option(options, "wordlist")
+                    .unwrap_or("wordlist.txt")
+                    .to_string(),
+                counter: integer_option(options, "counter").unwrap_or(0) as u32,
+                notes: string_option(options, "notes")
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            let response = truncate(format!("Saved {}.", describe(&entry)));
+
+            // Adding an already saved hint updates it:
+            vault::update(pswd_key, user_id, |entries| {
+                match entries.iter_mut().find(|e| e.hint == entry.hint) {
+                    Some(existing) => *existing = entry,
+                    None => entries.push(entry),
+                }
+            })?;
+            response
+        }
+        "remove" => {
+            let hint = string_option(options, "hint").unwrap_or_default();
+            let removed = vault::update(pswd_key, user_id, |entries| {
+                let len = entries.len();
+                entries.retain(|e| e.hint != hint);
+                entries.len() < len
+            })?;
+            if removed {
+                truncate(format!("Removed `{hint}` from your vault."))
+            } else {
+                truncate(format!("There is no hint `{hint}` in your vault."))
+            }
+        }
+        "show" => {
+            let entries = vault::load(pswd_key, user_id)?;
+            let hint = string_option(options, "hint").unwrap_or_default();
+            match entries.iter().find(|e| e.hint == hint) {
+                Some(entry) if entry.notes.is_empty() => truncate(describe(entry)),
+                Some(entry) => truncate(format!("{}\nNotes: {}", describe(entry), entry.notes)),
+                None => truncate(format!("There is no hint `{hint}` in your vault.")),
+            }
+        }
+        _ => return Err(Error::InvalidOptions("unknown hints subcommand")),
+    })
+}
+
+/// Suggests hints saved in the user's vault, fuzzily matched against what has been typed so far.
+pub fn suggest(typed: &str, pswd_key: &[u8], user_id: u64) -> Result<CreateAutocompleteResponse> {
+    let mut response = CreateAutocompleteResponse::new();
+    let entries = vault::load(pswd_key, user_id)?;
+
+    // Discord allows at most 25 suggestions, of at most 100 characters each:
+    let hints = entries
+        .iter()
+        .map(|entry| entry.hint.as_str())
+        .filter(|hint| hint.chars().count() <= 100);
+    for hint in fuzzy::matches(typed, hints).into_iter().take(25) {
+        response = response.add_string_choice(hint, hint);
+    }
+    Ok(response)
+}
+
+// Autocompletes the `hint` option of the subcommands. The vault can only be opened while the
+// user has an unlocked session.
+fn complete_hint(
+    options: &[ResolvedOption],
+    user_id: u64,
+    session_key: Option<&[u8]>,
+) -> Result<CreateAutocompleteResponse> {
+    let Some(ResolvedOption {
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        return Ok(CreateAutocompleteResponse::new());
+    };
+
+    let typed = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Autocomplete { value, .. } if option.name == "hint" => Some(value),
+            _ => None,
+        })
+        .unwrap_or_default();
+    match session_key {
+        Some(pswd_key) => suggest(typed, pswd_key, user_id),
+        None => Ok(CreateAutocompleteResponse::new()),
+    }
+}
+
+fn hint_option(description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, "hint", description).required(true)
+}
+
+fn saved_hint_option(description: &str) -> CreateCommandOption {
+    hint_option(description).set_autocomplete(true)
+}
+
+pub struct Hints;
+
+#[serenity::async_trait]
+impl Command for Hints {
+    fn name(&self) -> &'static str {
+        "hints"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name()).description("Manage your encrypted vault of password hints (after /unlock).")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "list", "List the hints saved in your vault."))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "add", "Save a hint (or update it, if already saved) in your vault.")
+                    .add_sub_option(hint_option("The tip used to generate/retrieve the password."))
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::Integer, "strength", "Strength of the password. Valid values are between 1 and 10 (included). Default is 6.")
+                            .min_int_value(1).max_int_value(10)
+                            .required(false))
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "wordlist", "Word list used to generate the password. Default is wordlist.txt.")
+                            .required(false))
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::Integer, "counter", "How many times the password has been rotated. Default is 0.")
+                            .min_int_value(0)
+                            .required(false))
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "notes", "Any notes about the password (where it is used, etc.).")
+                            .required(false)))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a hint from your vault.")
+                    .add_sub_option(saved_hint_option("The hint to remove.")))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "show", "Show everything saved about a hint in your vault.")
+                    .add_sub_option(saved_hint_option("The hint to show.")))
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        // The vault is only opened with the key of an unlocked session, so the secret words
+        // never end up in the command options (nor can they be guessed here, unthrottled):
+        let user_id = command.user.id.get();
+        let Some(session_key) = state.sessions.key(user_id) else {
+            return Ok(Response::text(
+                "/unlock a session first to open your hint vault.",
+            ));
+        };
+        let command = command.clone();
+        Ok(Response::blocking(state, move || {
+            manage(&command.data.options(), user_id, &session_key)
+        }))
+    }
+
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        autocomplete: &CommandInteraction,
+        state: &State,
+    ) -> Result<CreateAutocompleteResponse> {
+        let user_id = autocomplete.user.id.get();
+        let session_key = state.sessions.key(user_id);
+        complete_hint(
+            &autocomplete.data.options(),
+            user_id,
+            session_key.as_deref().map(Vec::as_slice),
+        )
+    }
+}
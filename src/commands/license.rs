@@ -16,28 +16,48 @@
  *  You should have received a copy of the GNU Affero General Public License
  *  along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
+use super::{Command, Response};
+use crate::error::Result;
+use crate::state::State;
 use serenity::builder::CreateCommand;
+use serenity::model::application::CommandInteraction;
+use serenity::prelude::Context;
 
-pub fn run() -> String {
-    "Mother Brain: Discord bot for kinda securely generating kinda secure \
-    passwords. \
-    Copyright (C) 2023-2024  Bolu <bolu@tuta.io>\n \
-    \n\
-    This program is free software: you can redistribute it and/or modify \
-    it under the terms of the GNU Affero General Public License as published \
-    by the Free Software Foundation, either version 3 of the License, or \
-    (at your option) any later version.\n\
-    \n\
-    This program is distributed in the hope that it will be useful, \
-    but WITHOUT ANY WARRANTY; without even the implied warranty of \
-    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the \
-    GNU Affero General Public License for more details.\n\
-    \n\
-    You should have received a copy of the GNU Affero General Public License \
-    along with this program. If not, see <https://www.gnu.org/licenses/>.\n"
-        .to_string()
-}
+pub struct License;
+
+#[serenity::async_trait]
+impl Command for License {
+    fn name(&self) -> &'static str {
+        "license"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name()).description("Show the software license for this bot.")
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("license").description("Show the software license for this bot.")
+    async fn run(
+        &self,
+        _ctx: &Context,
+        _command: &CommandInteraction,
+        _state: &State,
+    ) -> Result<Response> {
+        Ok(Response::text(
+            "Mother Brain: Discord bot for kinda securely generating kinda secure \
+        passwords. \
+        Copyright (C) 2023-2024  Bolu <bolu@tuta.io>\n \
+        \n\
+        This program is free software: you can redistribute it and/or modify \
+        it under the terms of the GNU Affero General Public License as published \
+        by the Free Software Foundation, either version 3 of the License, or \
+        (at your option) any later version.\n\
+        \n\
+        This program is distributed in the hope that it will be useful, \
+        but WITHOUT ANY WARRANTY; without even the implied warranty of \
+        MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the \
+        GNU Affero General Public License for more details.\n\
+        \n\
+        You should have received a copy of the GNU Affero General Public License \
+        along with this program. If not, see <https://www.gnu.org/licenses/>.\n",
+        ))
+    }
 }
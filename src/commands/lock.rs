@@ -0,0 +1,51 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{Command, Response};
+use crate::error::Result;
+use crate::state::State;
+use serenity::builder::CreateCommand;
+use serenity::model::application::CommandInteraction;
+use serenity::prelude::Context;
+
+pub struct Lock;
+
+#[serenity::async_trait]
+impl Command for Lock {
+    fn name(&self) -> &'static str {
+        "lock"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .description("Make me forget the secret words given with /unlock.")
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        if state.sessions.lock(command.user.id.get()) {
+            Ok(Response::text("Locked. I forgot your secret words."))
+        } else {
+            Ok(Response::text("There was no unlocked session."))
+        }
+    }
+}
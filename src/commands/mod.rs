@@ -16,7 +16,241 @@
  *  You should have received a copy of the GNU Affero General Public License
  *  along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
+pub mod breached;
 pub mod code;
 pub mod cracktime;
+pub mod hints;
 pub mod license;
+pub mod lock;
 pub mod pswd;
+pub mod recommend;
+pub mod strength;
+pub mod totp;
+pub mod unlock;
+
+use crate::error::{Error, Result};
+use crate::state::State;
+use serenity::builder::{
+    Builder, CreateAutocompleteResponse, CreateCommand, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateModal, EditInteractionResponse,
+};
+use serenity::model::application::{
+    ActionRowComponent, CommandInteraction, ModalInteraction, ModalInteractionData, ResolvedOption,
+    ResolvedValue,
+};
+use serenity::model::id::InteractionId;
+use serenity::prelude::Context;
+use std::{future::Future, pin::Pin};
+
+/// Discord refuses messages longer than 2000 characters, so responses stay well below that.
+pub const MAX_RESPONSE_LEN: usize = 1900;
+
+/// What a command answers with.
+pub enum Response {
+    /// A message, with any content, embeds or attachments.
+    Message(CreateInteractionResponseMessage),
+    /// A modal asking the user for more input. Its custom ID must start with the name of the
+    /// command, so its submission gets back to it.
+    Modal(CreateModal),
+    /// An acknowledgement sent right away, edited with the actual response once it's ready.
+    /// For work that could take longer than the 3 seconds Discord waits for a response.
+    Deferred(Pin<Box<dyn Future<Output = Result<EditInteractionResponse>> + Send>>),
+}
+
+impl Response {
+    /// An ephemeral text message (only visible to the user that triggered it).
+    pub fn text(content: impl Into<String>) -> Self {
+        Self::Message(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .ephemeral(true),
+        )
+    }
+
+    /// A deferred text message, produced by a CPU-bound job run in the blocking pool (e.g. one
+    /// deriving keys).
+    pub fn blocking(state: &State, job: impl FnOnce() -> Result<String> + Send + 'static) -> Self {
+        let pool = state.pool.clone();
+        Self::Deferred(Box::pin(async move {
+            let content = pool.run(job).await?;
+            Ok(EditInteractionResponse::new().content(content))
+        }))
+    }
+
+    /// Sends the response to the interaction it answers. If a deferred response fails, the
+    /// user is told so before returning the error.
+    pub async fn send(self, ctx: &Context, interaction: (InteractionId, &str)) -> Result<()> {
+        let (id, token) = interaction;
+        match self {
+            Self::Message(message) => Ok(CreateInteractionResponse::Message(message)
+                .execute(ctx, (id, token))
+                .await?),
+            Self::Modal(modal) => Ok(CreateInteractionResponse::Modal(modal)
+                .execute(ctx, (id, token))
+                .await?),
+            Self::Deferred(response) => {
+                CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                )
+                .execute(ctx, (id, token))
+                .await?;
+                match response.await {
+                    Ok(edit) => {
+                        edit.execute(ctx, token).await?;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        EditInteractionResponse::new()
+                            .content(err.user_message())
+                            .execute(ctx, token)
+                            .await?;
+                        Err(err)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[serenity::async_trait]
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn register(&self) -> CreateCommand;
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response>;
+
+    /// Suggests values for the option being typed.
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        _autocomplete: &CommandInteraction,
+        _state: &State,
+    ) -> Result<CreateAutocompleteResponse> {
+        Ok(CreateAutocompleteResponse::new())
+    }
+
+    /// Processes the submission of a modal shown by the command.
+    async fn submit(
+        &self,
+        _ctx: &Context,
+        _modal: &ModalInteraction,
+        _state: &State,
+    ) -> Result<Response> {
+        Err(Error::InvalidOptions("unexpected modal"))
+    }
+}
+
+/// Every command the bot registers and serves.
+pub fn registry() -> Vec<Box<dyn Command>> {
+    vec![
+        Box::new(pswd::Pswd),
+        Box::new(cracktime::Cracktime),
+        Box::new(recommend::Recommend),
+        Box::new(strength::Strength),
+        Box::new(breached::Breached),
+        Box::new(hints::Hints),
+        Box::new(unlock::Unlock),
+        Box::new(lock::Lock),
+        Box::new(totp::Totp),
+        Box::new(license::License),
+        Box::new(code::Code),
+    ]
+}
+
+/// Cuts a response down to `MAX_RESPONSE_LEN`, marking that it was cut.
+pub fn truncate(mut response: String) -> String {
+    if response.len() <= MAX_RESPONSE_LEN {
+        return response;
+    }
+    let mut end = MAX_RESPONSE_LEN - "...".len();
+    while !response.is_char_boundary(end) {
+        end -= 1;
+    }
+    response.truncate(end);
+    response.push_str("...");
+    response
+}
+
+/// Appends lines to a response while it stays within `MAX_RESPONSE_LEN`, then tells how many
+/// were left out.
+pub fn push_lines(response: &mut String, lines: impl ExactSizeIterator<Item = String>) {
+    let total = lines.len();
+    for (i, line) in lines.enumerate() {
+        if response.len() + line.len() > MAX_RESPONSE_LEN {
+            response.push_str(&format!("...and {} more.\n", total - i));
+            return;
+        }
+        response.push_str(&line);
+    }
+}
+
+/// Returns the value submitted for the text input with the given ID in a modal.
+pub fn input_text<'a>(modal: &'a ModalInteractionData, custom_id: &str) -> Option<&'a str> {
+    modal
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == custom_id => {
+                input.value.as_deref()
+            }
+            _ => None,
+        })
+}
+
+/// The value of the string option with the given name, if given.
+pub fn string_option<'a>(options: &'a [ResolvedOption], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::String(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+/// The value of the integer option with the given name, if given.
+pub fn integer_option(options: &[ResolvedOption], name: &str) -> Option<i64> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::Integer(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+/// The value of the boolean option with the given name, if given.
+pub fn boolean_option(options: &[ResolvedOption], name: &str) -> Option<bool> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::Boolean(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_responses_are_not_truncated() {
+        assert_eq!(truncate("Saved.".to_string()), "Saved.");
+    }
+
+    #[test]
+    fn long_responses_are_truncated_at_a_character() {
+        let response = truncate("é".repeat(MAX_RESPONSE_LEN));
+        assert!(response.len() <= MAX_RESPONSE_LEN);
+        assert!(response.ends_with("é..."));
+    }
+
+    #[test]
+    fn lines_that_do_not_fit_are_counted() {
+        let mut response = "Hints:\n".to_string();
+        let lines = vec!["- a hint\n".to_string(); 1000];
+        push_lines(&mut response, lines.into_iter());
+        assert!(response.len() <= MAX_RESPONSE_LEN + "...and 1000 more.\n".len());
+        let shown = response.matches("- a hint\n").count();
+        assert!(response.ends_with(&format!("...and {} more.\n", 1000 - shown)));
+    }
+}
@@ -20,76 +20,184 @@ extern crate rand;
 extern crate rand_chacha;
 extern crate rs_sha512;
 
+use super::{hints, input_text, integer_option, string_option, Command, Response};
+use crate::breaches::{Breaches, Found};
+use crate::error::{Error, Result};
+use crate::state::State;
 use openssl::symm::{decrypt, encrypt, Cipher};
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 use rs_sha512::Sha512State;
-use serenity::builder::{CreateCommand, CreateCommandOption};
-use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use serenity::builder::{
+    CreateActionRow, CreateAutocompleteResponse, CreateCommand, CreateCommandOption,
+    CreateInputText, CreateModal,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, InputTextStyle, ModalInteraction, ModalInteractionData,
+    ResolvedOption, ResolvedValue,
+};
+use serenity::prelude::Context;
 use std::{
     fs::{read_to_string, File},
     hash::{BuildHasher, Hash, Hasher},
     io::Read,
+    time::Instant,
 };
+use zeroize::Zeroizing;
 
-pub fn run(options: &[ResolvedOption]) -> String {
+/// The list of words passwords are made of.
+pub const WORDLIST: &str = "wordlist.txt";
+/// The key used to encrypt the passwords, aes256-encrypted with the secret words.
+pub const SECRET_FILE: &str = "secret256.dat";
+/// The most words a password can be made of.
+pub const MAX_STRENGTH: u32 = 10;
+
+fn request(
+    options: &[ResolvedOption],
+    session_key: Option<Zeroizing<Vec<u8>>>,
+    user_id: u64,
+    state: &State,
+) -> Result<Response> {
     // Default to 6 words for the password:
-    let num_words: u32;
-    if let Some(ResolvedOption {
-        value: ResolvedValue::Integer(nw),
-        ..
-    }) = options.get(2)
-    {
-        num_words = *nw as u32;
-    } else {
-        num_words = 6;
-    }
+    let num_words = match integer_option(options, "strength") {
+        Some(nw) if (1..=MAX_STRENGTH.into()).contains(&nw) => nw as u32,
+        Some(_) => {
+            return Err(Error::InvalidOptions(
+                "pswd command's strength is out of range",
+            ))
+        }
+        None => 6,
+    };
 
     // Extract password hint (used to generate the password):
-    let ResolvedOption {
-        value: ResolvedValue::String(w),
-        ..
-    } = options.first().unwrap()
-    else {
-        panic!("pswd command's hint is not a string (this message should never be printed).");
+    let Some(w) = string_option(options, "tip") else {
+        return Err(Error::InvalidOptions("pswd command's hint is missing"));
     };
-    let what = (*w).to_string();
-
-    // Extract secret key (used to aes256-decrypt initial data):
-    let ResolvedOption {
-        value: ResolvedValue::String(sk),
-        ..
-    } = options.get(1).unwrap()
-    else {
-        panic!(
-            "pswd command's secret words are not a string (this message should never be printed)."
-        );
+
+    // Users enrolled with /totp must give a code too:
+    let second_factor = state.totp.enrolled(user_id);
+
+    // With an unlocked session there is no need to ask for the secret words (nor for anything
+    // else, without a second factor):
+    if let (Some(pswd_key), false) = (&session_key, second_factor) {
+        if let Some(response) = throttle(state, user_id)? {
+            return Ok(response);
+        }
+        let (what, pswd_key) = (w.to_string(), pswd_key.clone());
+        let breaches = state.breaches.clone();
+        return Ok(deferred(state, move || {
+            derive(&what, &pswd_key, num_words, &breaches)
+        }));
+    }
+
+    // Ask for the rest through a modal, so the secret words don't stay in the input box or in
+    // the command history. (Discord has no masked text inputs, though.) The strength travels
+    // in the modal's ID, and the hint is pre-filled in the modal itself:
+    let mut inputs = vec![CreateActionRow::InputText(
+        CreateInputText::new(InputTextStyle::Short, "Tip", "tip").value(w),
+    )];
+    if session_key.is_none() {
+        inputs.push(CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Secret words", "secret_words")
+                .placeholder("The secret words to make me do work."),
+        ));
+    }
+    if second_factor {
+        inputs.push(CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Code", "code")
+                .placeholder("A code from your authenticator app.")
+                .min_length(6)
+                .max_length(6),
+        ));
+    }
+    Ok(Response::Modal(
+        CreateModal::new(format!("pswd:{num_words}"), "Generate/retrieve password")
+            .components(inputs),
+    ))
+}
+
+fn submit_secret_words(
+    modal: &ModalInteractionData,
+    user_id: u64,
+    state: &State,
+) -> Result<Response> {
+    if let Some(response) = throttle(state, user_id)? {
+        return Ok(response);
+    }
+
+    // Default to 6 words for the password. The modal's ID comes back from the client, so it
+    // could have been tampered with:
+    let num_words: u32 = match modal.custom_id.strip_prefix("pswd:") {
+        Some(nw) => match nw.parse() {
+            Ok(nw) if (1..=MAX_STRENGTH).contains(&nw) => nw,
+            _ => {
+                return Err(Error::InvalidOptions(
+                    "pswd modal's strength is out of range",
+                ))
+            }
+        },
+        None => 6,
+    };
+
+    // Extract password hint (used to generate the password):
+    let what = input_text(modal, "tip")
+        .ok_or(Error::InvalidOptions("pswd modal's hint is missing"))?
+        .to_string();
+
+    // Check the second factor of users enrolled with /totp (even if the modal didn't ask for
+    // it, in case they enrolled after it was shown):
+    if state.totp.enrolled(user_id)
+        && !state
+            .totp
+            .verify(user_id, input_text(modal, "code").unwrap_or_default())?
+    {
+        return Ok(Response::text("That code is not valid. Try again?"));
+    }
+
+    // Extract secret key (used to aes256-decrypt initial data). The modal only asks for it
+    // without an unlocked session:
+    let breaches = state.breaches.clone();
+    let Some(sk) = input_text(modal, "secret_words") else {
+        return Ok(match state.sessions.key(user_id) {
+            Some(pswd_key) => deferred(state, move || {
+                derive(&what, &pswd_key, num_words, &breaches)
+            }),
+            None => Response::text("Your session was locked meanwhile. Try again?"),
+        });
     };
-    let secret_key = &[(*sk).as_bytes(), &[0x62; 32]].concat()[0..32]; // Pad with 'b's until length 32
-                                                                       // (arbitrary character that can be typed as text).
+    let sk = Zeroizing::new(sk.to_string());
 
+    Ok(deferred(state, move || {
+        // Decrypt the key used to encrypt the password, using the secret key provided by the user:
+        let pswd_key = Zeroizing::new(pswd_key(&sk)?);
+        derive(&what, &pswd_key, num_words, &breaches)
+    }))
+}
+
+// Runs the derivation in the blocking pool, timing it.
+fn deferred(
+    state: &State,
+    derivation: impl FnOnce() -> Result<String> + Send + 'static,
+) -> Response {
+    let metrics = state.metrics.clone();
+    Response::blocking(state, move || {
+        let start = Instant::now();
+        let pswd = derivation();
+        metrics.observe_derivation(start.elapsed());
+        pswd
+    })
+}
+
+// Generates the password for the given hint and strength, encrypted with the password key.
+// Warns if it appears in the breach corpus.
+fn derive(what: &str, pswd_key: &[u8], num_words: u32, breaches: &Breaches) -> Result<String> {
     // Load list of words to compose the password:
-    let words: Vec<String> = read_to_string("wordlist.txt")
-        .expect("Could not open word-list file.")
+    let words: Vec<String> = read_to_string(WORDLIST)
+        .map_err(Error::io("Could not open word-list file"))?
         .lines()
         .map(String::from)
         .collect();
 
-    // Retrieve key used to encrypt the password, aes256-encryted:
-    let mut secret_file = File::open("secret256.dat").expect("Could not open secret256.dat.");
-    let mut secret = Vec::new();
-    secret_file
-        .read_to_end(&mut secret)
-        .expect("Could not read secret256.dat.");
-
-    // Decrypt the key used to encrypt the password, using the secret key provided by the user:
-    let pswd_key = match decrypt(Cipher::aes_256_cbc(), secret_key, None, &*secret) {
-        Ok(data) => data,
-        // On error (incorrect secret_key provided) use a fake key not to reveal the
-        // provided key was incorrect:
-        Err(_err) => [b"Nice try", secret_key, &[0; 24]].concat()[0..32].to_vec(),
-    };
-
     // Generate RNG seed from user-provided data:
     let mut hasher = Sha512State::default().build_hasher();
     // FIXME: The slice hotfix is for "legacy" compatibility reasons:
@@ -107,6 +215,16 @@ pub fn run(options: &[ResolvedOption]) -> String {
     for _ in 0..num_words {
         pswd.push_str(&format!("{} ", words[rng.gen_range(0..words.len())]));
     }
+
+    // Check the password as shown, and with its words run together:
+    let breached = [pswd.trim_end().to_string(), pswd.replace(' ', "")]
+        .map(Zeroizing::new)
+        .iter()
+        .map(|candidate| breaches.check(candidate))
+        .collect::<Result<Vec<Found>>>()?
+        .iter()
+        .any(|found| !matches!(found, Found::No));
+
     // Pad result with spaces to 512 characters
     // (so the ciphertext provides no information is given on the length of the decoded password's words):
     pswd.push_str(&String::from_iter([' '; 512]));
@@ -118,26 +236,129 @@ pub fn run(options: &[ResolvedOption]) -> String {
 
     // Encrypt the resposne (list of words composing the password) using the pswd_key as key,
     // and the pseudo-randomly-generated initiation vector.
-    let mut encrypted_data = encrypt(Cipher::aes_256_cbc(), &pswd_key, Some(iv), pswd.as_bytes())
-        .expect("AES encryption failed for the password.")
+    let mut encrypted_data = encrypt(Cipher::aes_256_cbc(), pswd_key, Some(iv), pswd.as_bytes())?
         .into_iter()
         .map(|b| format!("{:02x}", b)) // Format as hex string.
         .collect::<String>();
     encrypted_data.push_str(&iv_str); // Send IV together with encrypted data.
-    encrypted_data
+    if breached {
+        encrypted_data.push_str(
+            "\n\nWarning: this password appears in known breaches. Use another tip, or a \
+            higher strength.",
+        );
+    }
+    Ok(encrypted_data)
+}
+
+/// Counts an attempt of the user at deriving a password (which could be a guess of the secret
+/// words). Returns what to answer instead, if they have made too many.
+pub fn throttle(state: &State, user_id: u64) -> Result<Option<Response>> {
+    Ok(state.limits.attempt(user_id)?.map(|wait| {
+        Response::text(format!(
+            "Too many attempts. Try again in {} seconds.",
+            wait.as_secs().max(1)
+        ))
+    }))
+}
+
+/// Decrypts the key used to encrypt the passwords (stored aes256-encrypted in secret256.dat),
+/// using the secret words provided by the user. Each call is a guess of the secret words, so
+/// every caller must count it with [`throttle`] first.
+pub fn pswd_key(secret_words: &str) -> Result<Vec<u8>> {
+    let secret_key = &[secret_words.as_bytes(), &[0x62; 32]].concat()[0..32]; // Pad with 'b's until length 32
+                                                                              // (arbitrary character that can be typed as text).
+
+    // Retrieve key used to encrypt the password, aes256-encryted:
+    let mut secret_file =
+        File::open(SECRET_FILE).map_err(Error::io("Could not open secret256.dat"))?;
+    let mut secret = Vec::new();
+    secret_file
+        .read_to_end(&mut secret)
+        .map_err(Error::io("Could not read secret256.dat"))?;
+
+    Ok(
+        match decrypt(Cipher::aes_256_cbc(), secret_key, None, &secret) {
+            Ok(data) => data,
+            // On error (incorrect secret_key provided) use a fake key not to reveal the
+            // provided key was incorrect:
+            Err(_err) => [b"Nice try", secret_key, &[0; 24]].concat()[0..32].to_vec(),
+        },
+    )
+}
+
+// Suggests hints saved in the user's vault for the `tip` option. The vault can only be opened
+// while the user has an unlocked session.
+fn complete_tip(
+    options: &[ResolvedOption],
+    session_key: Option<&[u8]>,
+    user_id: u64,
+) -> Result<CreateAutocompleteResponse> {
+    let typed = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Autocomplete { value, .. } if option.name == "tip" => Some(value),
+            _ => None,
+        })
+        .unwrap_or_default();
+    match session_key {
+        Some(pswd_key) => hints::suggest(typed, pswd_key, user_id),
+        None => Ok(CreateAutocompleteResponse::new()),
+    }
 }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("pswd").description("Generate/retrieve password.")
-        .add_option(
-            CreateCommandOption::new(CommandOptionType::String, "tip", "A tip about the password you want to generate/retrieve.")
-                .required(true))
-        .add_option(
-            CreateCommandOption::new(CommandOptionType::String, "secret_words", "The secret words to make me do work.")
-                .required(true))
-        .add_option(
-            CreateCommandOption::new(CommandOptionType::Integer, "strength", "Strength of the generated password. Valid values are between 1 and 10 (included). Default is 6.")
-                .min_int_value(1).max_int_value(10)
-                .required(false)
+pub struct Pswd;
+
+#[serenity::async_trait]
+impl Command for Pswd {
+    fn name(&self) -> &'static str {
+        "pswd"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name()).description("Generate/retrieve password.")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "tip", "A tip about the password you want to generate/retrieve.")
+                    .set_autocomplete(true)
+                    .required(true))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "strength", "Strength of the generated password. Valid values are between 1 and 10 (included). Default is 6.")
+                    .min_int_value(1).max_int_value(MAX_STRENGTH.into())
+                    .required(false)
+            )
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        let user_id = command.user.id.get();
+        let session_key = state.sessions.key(user_id);
+        request(&command.data.options(), session_key, user_id, state)
+    }
+
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        autocomplete: &CommandInteraction,
+        state: &State,
+    ) -> Result<CreateAutocompleteResponse> {
+        let user_id = autocomplete.user.id.get();
+        let session_key = state.sessions.key(user_id);
+        complete_tip(
+            &autocomplete.data.options(),
+            session_key.as_deref().map(Vec::as_slice),
+            user_id,
         )
+    }
+
+    async fn submit(
+        &self,
+        _ctx: &Context,
+        modal: &ModalInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        submit_secret_words(&modal.data, modal.user.id.get(), state)
+    }
 }
@@ -0,0 +1,183 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::cracktime::{add_parameter_options, complete_models, dictionary_size, parameter_values};
+use super::{integer_option, string_option, Command, Response};
+use crate::attackers::Models;
+use crate::commands::pswd::MAX_STRENGTH;
+use crate::duration::YEAR;
+use crate::error::Result;
+use crate::state::State;
+use serenity::builder::{CreateAutocompleteResponse, CreateCommand, CreateCommandOption};
+use serenity::model::application::{CommandInteraction, CommandOptionType, ResolvedOption};
+use serenity::prelude::Context;
+
+// Alphabets of random-character passwords, to compare with:
+const ALPHABETS: [(&str, f64); 3] = [
+    ("lowercase letters", 26.0),
+    ("letters and digits", 62.0),
+    ("printable ASCII characters", 94.0),
+];
+
+fn recommend(options: &[ResolvedOption], models: &Models) -> Result<String> {
+    let years = integer_option(options, "years").unwrap_or(100);
+    let years_ahead = integer_option(options, "years_ahead");
+
+    // Default to the cheapest of the default hashes, and to the fastest hardware, to be on the
+    // safe side:
+    let hash = match string_option(options, "hash") {
+        Some(id) => match models.hash(id) {
+            Some(hash) => hash,
+            None => return Ok(format!("I don't know the hash `{id}`.")),
+        },
+        None => match (models.hashes.iter().filter(|hash| hash.default))
+            .min_by(|a, b| a.relative_cost.total_cmp(&b.relative_cost))
+        {
+            Some(hash) => hash,
+            None => return Ok("Choose a hash for me to assume.".to_string()),
+        },
+    };
+    let setup = hash.with(&parameter_values(options));
+    let hardware = match string_option(options, "hardware") {
+        Some(id) => match models.hardware(id) {
+            Some(hardware) => hardware,
+            None => return Ok(format!("I don't know the hardware `{id}`.")),
+        },
+        None => {
+            match (models.hardware.iter()).max_by(|a, b| a.sha256_rate.total_cmp(&b.sha256_rate)) {
+                Some(hardware) => hardware,
+                None => return Ok("Choose some hardware for me to assume.".to_string()),
+            }
+        }
+    };
+
+    // On average, passwords are found after trying half of the possible ones, so they need to
+    // have twice as many options as guesses can be made in time:
+    let seconds = years as f64 * YEAR;
+    let rate = hardware.guess_rate(&setup);
+    let guesses_log2 = match years_ahead {
+        Some(years_ahead) => models.projected_guesses_log2(seconds, rate, years_ahead as f64),
+        None => (seconds * rate).log2(),
+    };
+    let bits = (guesses_log2 + 1.0).max(0.0);
+
+    let dict_size = dictionary_size()?;
+    let strength = (bits / dict_size.log2()).ceil().max(1.0);
+
+    let mut response = format!(
+        "To hold out for {years} years on average against {}, with {} hashes",
+        hardware.description,
+        setup.name()
+    );
+    if let Some(years_ahead) = years_ahead {
+        response.push_str(&format!(
+            " (starting {years_ahead} years from now, on hardware getting twice as fast every {} years)",
+            models.doubling_years
+        ));
+    }
+    response.push_str(&format!(", a password needs {bits:.1} bits of entropy:\n"));
+    response.push_str(&format!(
+        "- Strength {strength} ({:.1} bits, with the {dict_size} words of my list).",
+        strength * dict_size.log2()
+    ));
+    if strength > MAX_STRENGTH as f64 {
+        response.push_str(&format!(
+            " That's more than /pswd goes up to ({MAX_STRENGTH})."
+        ));
+    }
+    response.push('\n');
+    for (alphabet, size) in ALPHABETS {
+        let length = (bits / f64::log2(size)).ceil().max(1.0);
+        response.push_str(&format!("- Or {length} random {alphabet}.\n"));
+    }
+
+    Ok(response)
+}
+
+pub struct Recommend;
+
+#[serenity::async_trait]
+impl Command for Recommend {
+    fn name(&self) -> &'static str {
+        "recommend"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let command = CreateCommand::new(self.name())
+            .description(
+                "Recommend a password strength that holds out against an attacker for long enough.",
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "years",
+                    "Years the password must hold out for, on average. Default is 100.",
+                )
+                .min_int_value(1)
+                .max_int_value(1_000_000_000)
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "hash",
+                    "Hash algorithm protecting the password. Default is SHA-256.",
+                )
+                .set_autocomplete(true)
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "hardware",
+                    "Hardware trying to crack the password. Default is the fastest I know of.",
+                )
+                .set_autocomplete(true)
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "years_ahead",
+                    "Assume the attack starts this many years from now, on ever faster hardware.",
+                )
+                .min_int_value(0)
+                .max_int_value(100)
+                .required(false),
+            );
+        add_parameter_options(command)
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        recommend(&command.data.options(), &state.models).map(Response::text)
+    }
+
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        autocomplete: &CommandInteraction,
+        state: &State,
+    ) -> Result<CreateAutocompleteResponse> {
+        Ok(complete_models(&autocomplete.data.options(), &state.models))
+    }
+}
@@ -0,0 +1,298 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::cracktime::{
+    add_parameter_options, complete_models, describe_time, parameter_values, PARAMETERS,
+};
+use super::{input_text, string_option, Command, Response};
+use crate::attackers::{Models, Setup};
+use crate::error::{Error, Result};
+use crate::state::State;
+use crate::strength::{self, Analysis, Match, Pattern};
+use serenity::builder::{
+    CreateActionRow, CreateAutocompleteResponse, CreateCommand, CreateCommandOption,
+    CreateInputText, CreateModal,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, InputTextStyle, ModalInteraction, ModalInteractionData,
+    ResolvedOption,
+};
+use serenity::model::Timestamp;
+use serenity::prelude::Context;
+use std::sync::Arc;
+use zeroize::Zeroizing;
+
+// Longer passwords take too long to analyze, and are strong enough anyway:
+const MAX_LENGTH: u16 = 100;
+
+// Hash parameter values, by name:
+type Values = Vec<(&'static str, f64)>;
+
+fn describe(m: &Match) -> String {
+    let what = match &m.pattern {
+        Pattern::Dictionary {
+            dictionary,
+            rank,
+            reversed,
+            l33t,
+            capitals,
+        } => {
+            let mut what = match rank {
+                Some(rank) => format!("entry #{rank} of my {dictionary}"),
+                None => format!("a word of my {dictionary}"),
+            };
+            let modifiers = [
+                (*reversed, "reversed"),
+                (*l33t, "with l33t substitutions"),
+                (*capitals, "with capitals"),
+            ];
+            for (_, modifier) in modifiers.iter().filter(|(applies, _)| *applies) {
+                what.push_str(&format!(", {modifier}"));
+            }
+            what
+        }
+        Pattern::Keyboard { turns: 1 } => "a straight walk on the keyboard".to_string(),
+        Pattern::Keyboard { turns } => format!("a walk on the keyboard, with {turns} turns"),
+        Pattern::Date => "a date".to_string(),
+        Pattern::Year => "a year".to_string(),
+        Pattern::Repeat { times } => format!("something repeated {times} times"),
+        Pattern::Sequence => "a sequence".to_string(),
+        Pattern::Bruteforce => "random-looking characters".to_string(),
+    };
+    // Only positions are given, so the password doesn't show up in the response:
+    format!(
+        "- Characters {} to {}: {what} ({:.1} bits).\n",
+        m.start + 1,
+        m.end,
+        m.guesses.log2()
+    )
+}
+
+fn report(analysis: &Analysis, hashes: &[Setup], models: &Models, small: bool) -> String {
+    let mut response = format!(
+        "That password would take about {:.3e} guesses ({:.1} bits) to find:\n",
+        analysis.guesses,
+        analysis.bits()
+    );
+    for m in &analysis.matches {
+        response.push_str(&describe(m));
+    }
+    if small {
+        response.push_str(
+            "-# My dictionaries of common passwords and words are small, so it could well take \
+             far fewer guesses.\n",
+        );
+    }
+
+    // Attackers try the most likely passwords first, so they need that many guesses (rather
+    // than half the possible passwords):
+    for hash in hashes {
+        response.push_str(&format!("\n**Assuming {} hashes:**\n", hash.name()));
+        for hardware in &models.hardware {
+            let seconds = analysis.guesses / hardware.guess_rate(hash);
+            response.push_str(&format!(
+                "With {}: {}.\n",
+                hardware.description,
+                describe_time(seconds, false)
+            ));
+        }
+    }
+    response
+}
+
+fn analyze(modal: &ModalInteractionData, state: &State) -> Result<Response> {
+    let password = Zeroizing::new(
+        input_text(modal, "password")
+            .ok_or(Error::InvalidOptions(
+                "strength modal's password is missing",
+            ))?
+            .to_string(),
+    );
+    let (hash, values) = parse_custom_id(&modal.custom_id)?;
+    let hash = hash.map(str::to_string);
+    let models = Arc::clone(&state.models);
+    let dictionaries = Arc::clone(&state.dictionaries);
+    let this_year = Timestamp::now().year();
+
+    Ok(Response::blocking(state, move || {
+        let hashes: Vec<Setup> = match &hash {
+            Some(id) => match models.hash(id) {
+                Some(hash) => vec![hash.with(&values)],
+                None => return Ok(format!("I don't know the hash `{id}`.")),
+            },
+            None => (models.hashes.iter())
+                .filter(|hash| hash.default)
+                .map(|hash| hash.with(&values))
+                .collect(),
+        };
+        let analysis = strength::analyze(&password, &dictionaries, this_year);
+        Ok(report(&analysis, &hashes, &models, dictionaries.small()))
+    }))
+}
+
+// The hash and its parameters, if any, travel in the modal's ID (e.g.
+// "strength:bcrypt:cost=10"). The ID comes back from the client, so it could have been
+// tampered with:
+fn parse_custom_id(custom_id: &str) -> Result<(Option<&str>, Values)> {
+    let invalid = || Error::InvalidOptions("strength modal's ID is malformed");
+    let rest = match custom_id.strip_prefix("strength") {
+        Some("") => return Ok((None, Vec::new())),
+        Some(rest) => rest.strip_prefix(':').ok_or_else(invalid)?,
+        None => return Err(invalid()),
+    };
+    let (hash, parameters) = rest.split_once(':').unwrap_or((rest, ""));
+    let hash = Some(hash).filter(|hash| !hash.is_empty());
+
+    let mut values = Vec::new();
+    for parameter in parameters
+        .split(',')
+        .filter(|parameter| !parameter.is_empty())
+    {
+        let (name, value) = parameter.split_once('=').ok_or_else(invalid)?;
+        let &(name, _, min, max) = (PARAMETERS.iter())
+            .find(|(known, ..)| *known == name)
+            .ok_or_else(invalid)?;
+        match value.parse::<u64>() {
+            Ok(value) if value >= min && max.is_none_or(|max| value <= max) => {
+                values.push((name, value as f64))
+            }
+            _ => {
+                return Err(Error::InvalidOptions(
+                    "strength modal's hash parameters are out of range",
+                ))
+            }
+        }
+    }
+    Ok((hash, values))
+}
+
+// Asks for the password through a modal, so it doesn't stay in the input box or in the
+// command history (nor in the audit log, which records options named like hints):
+fn request(options: &[ResolvedOption]) -> Response {
+    let hash = string_option(options, "hash").unwrap_or_default();
+    let parameters: Vec<String> = (parameter_values(options).into_iter())
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    let custom_id = match (hash, parameters.is_empty()) {
+        ("", true) => "strength".to_string(),
+        (hash, _) => format!("strength:{hash}:{}", parameters.join(",")),
+    };
+    Response::Modal(
+        CreateModal::new(custom_id, "Analyze password strength").components(vec![
+            CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Short, "Password", "password")
+                    .placeholder("It's never logged nor kept.")
+                    .max_length(MAX_LENGTH),
+            ),
+        ]),
+    )
+}
+
+pub struct Strength;
+
+#[serenity::async_trait]
+impl Command for Strength {
+    fn name(&self) -> &'static str {
+        "strength"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let command = CreateCommand::new(self.name())
+            .description("Estimate how hard to crack any password is.")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "hash",
+                    "Hash algorithm protecting the password. Default is SHA-256 and bcrypt.",
+                )
+                .set_autocomplete(true)
+                .required(false),
+            );
+        add_parameter_options(command)
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        _state: &State,
+    ) -> Result<Response> {
+        Ok(request(&command.data.options()))
+    }
+
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        autocomplete: &CommandInteraction,
+        state: &State,
+    ) -> Result<CreateAutocompleteResponse> {
+        Ok(complete_models(&autocomplete.data.options(), &state.models))
+    }
+
+    async fn submit(
+        &self,
+        _ctx: &Context,
+        modal: &ModalInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        analyze(&modal.data, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_ids_carry_the_hash_and_its_parameters() {
+        assert_eq!(parse_custom_id("strength").unwrap(), (None, Vec::new()));
+        assert_eq!(
+            parse_custom_id("strength:bcrypt:").unwrap(),
+            (Some("bcrypt"), Vec::new())
+        );
+        assert_eq!(
+            parse_custom_id("strength:argon2id:memory_kib=19456,time=2").unwrap(),
+            (
+                Some("argon2id"),
+                vec![("memory_kib", 19456.0), ("time", 2.0)]
+            )
+        );
+        assert_eq!(
+            parse_custom_id("strength::cost=12").unwrap(),
+            (None, vec![("cost", 12.0)])
+        );
+    }
+
+    #[test]
+    fn tampered_custom_ids_are_rejected() {
+        for custom_id in [
+            "strengthy",
+            "strength:bcrypt:cost",
+            "strength:bcrypt:rounds=5",
+            "strength:bcrypt:cost=3",
+            "strength:bcrypt:cost=32",
+            "strength:bcrypt:cost=-1",
+            "strength:pbkdf2-sha256:iterations=many",
+        ] {
+            assert!(
+                matches!(parse_custom_id(custom_id), Err(Error::InvalidOptions(_))),
+                "{custom_id} was accepted"
+            );
+        }
+    }
+}
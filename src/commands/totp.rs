@@ -0,0 +1,138 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{string_option, Command, Response};
+use crate::commands::pswd::throttle;
+use crate::error::{Error, Result};
+use crate::state::State;
+use crate::totp::qr_png;
+use serenity::builder::{
+    CreateAttachment, CreateCommand, CreateCommandOption, CreateInteractionResponseMessage,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::prelude::Context;
+
+fn manage(options: &[ResolvedOption], user_id: u64, state: &State) -> Result<Response> {
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        return Err(Error::InvalidOptions(
+            "totp command was invoked without a subcommand",
+        ));
+    };
+    let code = string_option(options, "code").unwrap_or_default();
+
+    // Every code checked could be a guess (of the one that disables the second factor, above
+    // all), so it counts as an attempt, same as the secret words:
+    if *subcommand != "enroll" {
+        if let Some(response) = throttle(state, user_id)? {
+            return Ok(response);
+        }
+    }
+
+    Ok(match *subcommand {
+        "enroll" => {
+            let Some(uri) = state.totp.enroll(user_id)? else {
+                return Ok(Response::text(
+                    "You are already enrolled. /totp disable it first to enroll again.",
+                ));
+            };
+            // The secret is only ever shown here:
+            Response::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!(
+                        "Scan the QR code with your authenticator app (or add this URI to it):\n\
+                         `{uri}`\n\
+                         I will not show it again. Then, confirm it with /totp confirm and a \
+                         code from the app, and I will ask for a code every time you use /pswd."
+                    ))
+                    .add_file(CreateAttachment::bytes(qr_png(&uri)?, "totp.png"))
+                    .ephemeral(true),
+            )
+        }
+        "confirm" => Response::text(match state.totp.verify(user_id, code)? {
+            true => "Confirmed. I will ask for a code every time you use /pswd.",
+            false => "That code is not valid. Did you /totp enroll first?",
+        }),
+        "disable" => Response::text(match state.totp.disable(user_id, code)? {
+            true => "Disabled. I will not ask for codes anymore.",
+            false => "That code is not valid.",
+        }),
+        _ => return Err(Error::InvalidOptions("unknown totp subcommand")),
+    })
+}
+
+fn code_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "code",
+        "A code from your authenticator app.",
+    )
+    .min_length(6)
+    .max_length(6)
+    .required(true)
+}
+
+pub struct Totp;
+
+#[serenity::async_trait]
+impl Command for Totp {
+    fn name(&self) -> &'static str {
+        "totp"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .description("Require codes from an authenticator app to get passwords.")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "enroll",
+                "Get the secret to add to your authenticator app.",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "confirm",
+                    "Confirm the enrollment with a first code.",
+                )
+                .add_sub_option(code_option()),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "disable",
+                    "Stop requiring codes.",
+                )
+                .add_sub_option(code_option()),
+            )
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        command: &CommandInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        manage(&command.data.options(), command.user.id.get(), state)
+    }
+}
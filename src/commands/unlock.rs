@@ -0,0 +1,93 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{input_text, Command, Response};
+use crate::commands::pswd::{pswd_key, throttle};
+use crate::error::{Error, Result};
+use crate::state::State;
+use serenity::builder::{CreateActionRow, CreateCommand, CreateInputText, CreateModal};
+use serenity::model::application::{CommandInteraction, InputTextStyle, ModalInteraction};
+use serenity::prelude::Context;
+use zeroize::Zeroizing;
+
+pub struct Unlock;
+
+#[serenity::async_trait]
+impl Command for Unlock {
+    fn name(&self) -> &'static str {
+        "unlock"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .description("Give me your secret words once, instead of for every command.")
+    }
+
+    async fn run(
+        &self,
+        _ctx: &Context,
+        _command: &CommandInteraction,
+        _state: &State,
+    ) -> Result<Response> {
+        Ok(Response::Modal(
+            CreateModal::new(self.name(), "Unlock session").components(vec![
+                CreateActionRow::InputText(
+                    CreateInputText::new(InputTextStyle::Short, "Secret words", "secret_words")
+                        .placeholder("The secret words to make me do work."),
+                ),
+            ]),
+        ))
+    }
+
+    async fn submit(
+        &self,
+        _ctx: &Context,
+        modal: &ModalInteraction,
+        state: &State,
+    ) -> Result<Response> {
+        // Unlocking takes the same secret words as /pswd, so it counts as an attempt too:
+        let user_id = modal.user.id.get();
+        if let Some(response) = throttle(state, user_id)? {
+            return Ok(response);
+        }
+
+        let sk = Zeroizing::new(
+            input_text(&modal.data, "secret_words")
+                .ok_or(Error::InvalidOptions(
+                    "unlock modal's secret words are missing",
+                ))?
+                .to_string(),
+        );
+
+        let sessions = state.sessions.clone();
+        Ok(Response::blocking(state, move || {
+            // Incorrect secret words also unlock a session (with the fake key), not to reveal
+            // they were incorrect:
+            sessions.unlock(user_id, pswd_key(&sk)?);
+
+            let timeout = sessions.idle_timeout().as_secs();
+            let timeout = match timeout {
+                0..=119 => format!("{timeout} seconds"),
+                _ => format!("{} minutes", timeout / 60),
+            };
+            Ok(format!(
+                "Unlocked. I will forget your secret words after {timeout} of inactivity, or when you /lock."
+            ))
+        }))
+    }
+}
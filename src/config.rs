@@ -0,0 +1,261 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::error::{Error, Result};
+use crate::policy::Policy;
+use serde::Deserialize;
+use std::{
+    collections::HashMap, env, fs::read_to_string, io::ErrorKind, net::SocketAddr,
+    num::NonZeroUsize, thread, time::Duration,
+};
+
+/// Bot configuration, read from `config.toml` (or the file in `MOTHER_BRAIN_CONFIG`).
+/// Every setting is optional; missing ones take their default value.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub alerts: AlertsConfig,
+    pub audit: AuditConfig,
+    pub breaches: BreachesConfig,
+    pub cracktime: CracktimeConfig,
+    pub derivations: DerivationsConfig,
+    pub logging: LoggingConfig,
+    pub metrics: MetricsConfig,
+    pub policy: Policy,
+    pub rate_limits: RateLimitsConfig,
+    pub sessions: SessionsConfig,
+    pub strength: StrengthConfig,
+    pub totp: TotpConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// Users to tell about unauthorized attempts, by direct message.
+    pub owners: Vec<u64>,
+    /// Seconds to gather attempts for, before sending them all in a single alert.
+    pub window_secs: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            owners: vec![231844961878802442],
+            window_secs: 60,
+        }
+    }
+}
+
+impl AlertsConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs.max(1))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// The audit log, one JSON entry per line. Its head is kept next to it, in `<file>.head`.
+    pub file: String,
+    /// Random salt for hashing the hints in the log, created on first run. Keep it secret:
+    /// without it, the hashes can't be matched against guessed hints.
+    pub salt_file: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            file: "audit.log".to_string(),
+            salt_file: "audit.salt".to_string(),
+        }
+    }
+}
+
+impl AuditConfig {
+    pub fn head_file(&self) -> String {
+        format!("{}.head", self.file)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BreachesConfig {
+    /// SHA-1 hashes of breached passwords, sorted, in the format of Pwned Passwords.
+    pub hashes_file: Option<String>,
+    /// A Bloom filter of those hashes, built with `build-bloom`. Much smaller, but with some
+    /// false positives. Only used without `hashes_file`.
+    pub bloom_file: Option<String>,
+    /// Share of passwords the Bloom filter wrongly reports as breached, when building it.
+    pub false_positive_rate: f64,
+}
+
+impl Default for BreachesConfig {
+    fn default() -> Self {
+        Self {
+            hashes_file: None,
+            bloom_file: None,
+            false_positive_rate: 0.001,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CracktimeConfig {
+    /// Attacker models to use instead of the ones shipped with the bot
+    /// (see `data/attackers.toml`).
+    pub models_file: Option<String>,
+    /// Price of electricity, in US dollars per kWh, instead of the one in the models.
+    pub price_per_kwh: Option<f64>,
+    /// Years it takes for hardware to get twice as fast, instead of the one in the models.
+    pub doubling_years: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DerivationsConfig {
+    /// How many keys and passwords can be derived at once. Defaults to the number of CPUs.
+    pub max_concurrent: usize,
+}
+
+impl Default for DerivationsConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable, multi-line output.
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Address to serve `/healthz` and `/metrics` on. Not served unless set.
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RateLimitsConfig {
+    /// Attempts at secret words a user can make in a row.
+    pub user_burst: f64,
+    /// Attempts a user gets back per minute.
+    pub user_per_minute: f64,
+    /// Attempts all users together can make in a row.
+    pub global_burst: f64,
+    /// Attempts all users together get back per minute.
+    pub global_per_minute: f64,
+    /// Seconds a user is locked out the first time they run out of attempts. Doubles every
+    /// time they run out again, until their attempts have been fully restored.
+    pub lockout_base_secs: u64,
+    /// Longest lockout, in seconds.
+    pub lockout_max_secs: u64,
+    /// Where to keep the attempts left, so they survive restarts.
+    pub state_file: String,
+    /// Seconds a user must wait between two uses of a command, by command name.
+    pub cooldowns: HashMap<String, u64>,
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            user_burst: 5.0,
+            user_per_minute: 1.0,
+            global_burst: 30.0,
+            global_per_minute: 30.0,
+            lockout_base_secs: 60,
+            lockout_max_secs: 24 * 60 * 60,
+            state_file: "ratelimits.json".to_string(),
+            cooldowns: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SessionsConfig {
+    /// Seconds of inactivity after which an unlocked session is locked again.
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: 300,
+        }
+    }
+}
+
+impl SessionsConfig {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct StrengthConfig {
+    /// Common passwords, most common first, one per line, to use for /strength instead of the
+    /// few shipped with the bot.
+    pub passwords_file: Option<String>,
+    /// Common English words, most common first, one per line, to use for /strength instead of
+    /// the few shipped with the bot.
+    pub words_file: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TotpConfig {
+    /// Where to keep the users' TOTP secrets. Keep it private: anyone who reads it can
+    /// generate their codes.
+    pub store_file: String,
+}
+
+impl Default for TotpConfig {
+    fn default() -> Self {
+        Self {
+            store_file: "totp.json".to_string(),
+        }
+    }
+}
+
+pub fn load() -> Result<Config> {
+    let path = env::var("MOTHER_BRAIN_CONFIG").unwrap_or_else(|_| "config.toml".to_string());
+    match read_to_string(&path) {
+        Ok(contents) => Ok(toml::from_str(&contents)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(Error::io(format!(
+            "Could not read configuration file {path}"
+        ))(err)),
+    }
+}
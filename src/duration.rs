@@ -0,0 +1,187 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds in an hour.
+pub const HOUR: f64 = 3600.0;
+/// Seconds in a day.
+pub const DAY: f64 = 24.0 * HOUR;
+/// Seconds in a year, taken as 365 days everywhere in the bot.
+pub const YEAR: f64 = 365.0 * DAY;
+const UNITS: [(&str, f64); 5] = [
+    ("second", 1.0),
+    ("minute", 60.0),
+    ("hour", HOUR),
+    ("day", DAY),
+    ("year", YEAR),
+];
+// Names for every power of a thousand years:
+const SCALES: [&str; 10] = [
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+    "sextillion",
+    "septillion",
+    "octillion",
+    "nonillion",
+];
+
+const AGE_OF_THE_UNIVERSE: f64 = 13.8e9 * YEAR;
+// Milestones of the universe's slow heat death. Sources:
+//  * https://en.wikipedia.org/wiki/Future_of_an_expanding_universe
+const LAST_STARS: f64 = 1e14 * YEAR;
+const LAST_BLACK_HOLES: f64 = 1e100 * YEAR;
+
+// Rounds to two significant digits for small values:
+fn round(value: f64) -> f64 {
+    if value < 10.0 {
+        (value * 10.0).round() / 10.0
+    } else {
+        value.round()
+    }
+}
+
+/// Describes a duration in the unit that makes the most sense for it, e.g. "about 3 minutes"
+/// or "about 4.2 thousand years".
+pub fn humanize(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "forever".to_string();
+    }
+    if seconds < 1.0 {
+        return "less than a second".to_string();
+    }
+
+    let index = UNITS
+        .iter()
+        .rposition(|(_, length)| seconds >= *length)
+        .unwrap_or(0);
+    let (mut unit, length) = UNITS[index];
+    let mut value = round(seconds / length);
+    // Rounding may carry over into the next unit (e.g. 59.96 seconds into 60):
+    if let Some(&(next_unit, next_length)) = UNITS.get(index + 1) {
+        if value * length >= next_length {
+            unit = next_unit;
+            value = round(value * length / next_length);
+        }
+    }
+
+    // Count years in thousands, millions...:
+    let mut scale = "";
+    if unit == "year" && value >= 1000.0 {
+        let years = value;
+        let mut power = (years.log10() / 3.0).floor() as usize;
+        value = round(years / 1000f64.powi(power as i32));
+        // Here too, rounding may carry over into the next scale:
+        if value >= 1000.0 {
+            power += 1;
+            value /= 1000.0;
+        }
+        if power > SCALES.len() {
+            return format!("about {years:.1e} years");
+        }
+        scale = SCALES[power - 1];
+    }
+
+    let plural = if value == 1.0 && scale.is_empty() {
+        ""
+    } else {
+        "s"
+    };
+    match scale {
+        "" => format!("about {value} {unit}{plural}"),
+        _ => format!("about {value} {scale} {unit}{plural}"),
+    }
+}
+
+/// Puts very long durations into perspective, comparing them to the age of the universe or
+/// to the milestones of its heat death.
+pub fn compare(seconds: f64) -> Option<String> {
+    if seconds >= LAST_BLACK_HOLES {
+        Some("after the last black holes have evaporated".to_string())
+    } else if seconds >= LAST_STARS {
+        Some("after the last stars have burned out".to_string())
+    } else if seconds >= AGE_OF_THE_UNIVERSE {
+        Some(format!(
+            "{}× the age of the universe",
+            round(seconds / AGE_OF_THE_UNIVERSE)
+        ))
+    } else {
+        None
+    }
+}
+
+/// Time since the Unix epoch (zero if the clock is set before it).
+pub fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn humanize_picks_the_largest_unit() {
+        assert_eq!(humanize(0.5), "less than a second");
+        assert_eq!(humanize(1.0), "about 1 second");
+        assert_eq!(humanize(45.0), "about 45 seconds");
+        assert_eq!(humanize(90.0), "about 1.5 minutes");
+        assert_eq!(humanize(3.0 * HOUR), "about 3 hours");
+        assert_eq!(humanize(DAY), "about 1 day");
+        assert_eq!(humanize(12.0 * YEAR), "about 12 years");
+    }
+
+    #[test]
+    fn humanize_counts_years_in_thousands() {
+        assert_eq!(humanize(4200.0 * YEAR), "about 4.2 thousand years");
+        assert_eq!(humanize(1e6 * YEAR), "about 1 million years");
+        assert_eq!(humanize(35e9 * YEAR), "about 35 billion years");
+        assert_eq!(humanize(1e40 * YEAR), "about 1.0e40 years");
+    }
+
+    #[test]
+    fn humanize_rounds_before_picking_the_unit() {
+        assert_eq!(humanize(59.96), "about 1 minute");
+        assert_eq!(humanize(23.97 * HOUR), "about 1 day");
+        assert_eq!(humanize(999.96 * YEAR), "about 1 thousand years");
+        assert_eq!(humanize(999_960.0 * YEAR), "about 1 million years");
+        assert_eq!(humanize(9.96 * YEAR), "about 10 years");
+    }
+
+    #[test]
+    fn humanize_handles_infinity() {
+        assert_eq!(humanize(f64::INFINITY), "forever");
+        assert_eq!(humanize(f64::NAN), "forever");
+    }
+
+    #[test]
+    fn compare_only_puts_very_long_durations_into_perspective() {
+        assert_eq!(compare(1e6 * YEAR), None);
+        assert_eq!(
+            compare(2.0 * AGE_OF_THE_UNIVERSE).as_deref(),
+            Some("2× the age of the universe")
+        );
+        assert!(compare(1e20 * YEAR).unwrap().contains("stars"));
+        assert!(compare(1e200 * YEAR).unwrap().contains("black holes"));
+    }
+}
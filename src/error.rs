@@ -0,0 +1,139 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::{fmt, io};
+
+/// Everything that can go wrong while serving a command.
+#[derive(Debug)]
+pub enum Error {
+    /// A file the bot needs could not be read or written.
+    Io(String, io::Error),
+    /// Some encryption or decryption operation failed.
+    Crypto(openssl::error::ErrorStack),
+    /// Some data stored on disk could not be (de)serialized.
+    Serialization(serde_json::Error),
+    /// The configuration file is not valid.
+    Config(toml::de::Error),
+    /// Talking to Discord failed.
+    Discord(Box<serenity::Error>),
+    /// A job run in the background panicked (or was cancelled).
+    Task(tokio::task::JoinError),
+    /// The rate limits make no sense (e.g. negative rates).
+    InvalidRateLimits(String),
+    /// The attacker models for /cracktime are inconsistent.
+    InvalidModels(String),
+    /// The audit log has been tampered with (or got corrupted).
+    InvalidAuditLog(String),
+    /// A hint vault could not be decrypted, despite being named after the key (so it got
+    /// corrupted).
+    InvalidVault,
+    /// The breach corpus is not in any known format.
+    InvalidBreaches(String),
+    /// A chart could not be drawn.
+    Chart(String),
+    /// A command received options it doesn't know how to handle.
+    InvalidOptions(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Wraps an I/O error with a description of what was being done.
+    pub fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let context = context.into();
+        move |err| Self::Io(context, err)
+    }
+
+    /// What to tell the user. The details only go to the logs, as they could be sensitive.
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Self::InvalidOptions(_) => "I did not understand that command. Try it again?",
+            _ => "Something went wrong on my side, sorry. The details are in my logs.",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(context, err) => write!(f, "{context}: {err}"),
+            Self::Crypto(err) => write!(f, "cryptographic operation failed: {err}"),
+            Self::Serialization(err) => write!(f, "(de)serialization failed: {err}"),
+            Self::Config(err) => write!(f, "erroneous configuration: {err}"),
+            Self::Discord(err) => write!(f, "Discord request failed: {err}"),
+            Self::Task(err) => write!(f, "background job failed: {err}"),
+            Self::InvalidRateLimits(why) => write!(f, "invalid rate limits: {why}"),
+            Self::InvalidModels(why) => write!(f, "invalid attacker models: {why}"),
+            Self::InvalidAuditLog(why) => write!(f, "invalid audit log: {why}"),
+            Self::InvalidVault => write!(f, "corrupted hint vault"),
+            Self::InvalidBreaches(why) => write!(f, "invalid breach corpus: {why}"),
+            Self::Chart(why) => write!(f, "could not draw chart: {why}"),
+            Self::InvalidOptions(what) => write!(f, "invalid command options: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            Self::Crypto(err) => Some(err),
+            Self::Serialization(err) => Some(err),
+            Self::Config(err) => Some(err),
+            Self::Discord(err) => Some(err),
+            Self::Task(err) => Some(err),
+            Self::InvalidRateLimits(_)
+            | Self::InvalidModels(_)
+            | Self::InvalidAuditLog(_)
+            | Self::InvalidVault
+            | Self::InvalidBreaches(_)
+            | Self::Chart(_)
+            | Self::InvalidOptions(_) => None,
+        }
+    }
+}
+
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(err: openssl::error::ErrorStack) -> Self {
+        Self::Crypto(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(err: serenity::Error) -> Self {
+        Self::Discord(Box::new(err))
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Self::Task(err)
+    }
+}
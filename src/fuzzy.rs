@@ -0,0 +1,108 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+/// Scores how well `query` fuzzily matches `candidate` (case-insensitively), or returns `None`
+/// if the characters of `query` don't all appear, in order, in `candidate`.
+///
+/// Higher scores are better matches: consecutive characters and characters at the start of
+/// words score extra, while gaps between matched characters are penalized.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut last_match: Option<usize> = None;
+    let mut pos = 0;
+
+    for q in query.to_lowercase().chars() {
+        let found = pos + candidate[pos..].iter().position(|&c| c == q)?;
+
+        score += 1;
+        match last_match {
+            Some(last) if last + 1 == found => score += 5, // Consecutive characters.
+            Some(last) => score -= (found - last - 1).min(5) as i64, // Gap between characters.
+            None => score -= found.min(5) as i64,          // Leading gap.
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3; // Start of a word.
+        }
+
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
+
+/// Returns the candidates that fuzzily match `query`, best matches first.
+pub fn matches<'a>(query: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut scored: Vec<(i64, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| Some((score(query, candidate)?, candidate)))
+        .collect();
+    // Best score first; shorter candidates first on ties:
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.len().cmp(&b.1.len())));
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters_must_all_appear_in_order() {
+        assert_eq!(score("xyz", "bcrypt"), None);
+        assert_eq!(score("tpyrcb", "bcrypt"), None);
+        assert_eq!(score("bcryptt", "bcrypt"), None);
+        assert!(score("bcr", "bcrypt").is_some());
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        assert_eq!(score("SHA", "sha256"), score("sha", "SHA-256"));
+    }
+
+    #[test]
+    fn consecutive_characters_score_higher() {
+        assert!(score("bcr", "bcrypt") > score("bcr", "bacar"));
+    }
+
+    #[test]
+    fn word_starts_score_higher() {
+        assert!(score("r", "rtx-4090") > score("r", "ertx"));
+        assert!(score("4090", "rtx-4090") > score("4090", "rtx14090"));
+    }
+
+    #[test]
+    fn best_matches_come_first() {
+        let candidates = ["pbkdf2-sha256", "sha256", "sha1", "md5"];
+        assert_eq!(
+            matches("sha", candidates),
+            ["sha1", "sha256", "pbkdf2-sha256"]
+        );
+        assert_eq!(matches("md", candidates), ["md5"]);
+        assert!(matches("argon", candidates).is_empty());
+    }
+
+    #[test]
+    fn ties_go_to_the_shorter_candidate() {
+        assert_eq!(score("gpu", "gpu-cluster"), score("gpu", "gpu"));
+        assert_eq!(
+            matches("gpu", ["gpu-cluster", "gpu"]),
+            ["gpu", "gpu-cluster"]
+        );
+    }
+}
@@ -16,78 +16,295 @@
  *  You should have received a copy of the GNU Affero General Public License
  *  along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
+mod alerts;
+mod attackers;
+mod audit;
+mod breaches;
+mod chart;
 mod commands;
+mod config;
+mod duration;
+mod error;
+mod fuzzy;
+mod logging;
+mod metrics;
+mod monitor;
+mod policy;
+mod pool;
+mod ratelimit;
+mod session;
+mod shutdown;
+mod state;
+mod storage;
+mod strength;
+mod totp;
+mod vault;
 
-use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage};
+use alerts::{Alerts, Attempt};
+use attackers::Models;
+use audit::AuditLog;
+use breaches::Breaches;
+use commands::Response;
+use metrics::Metrics;
+use policy::{Caller, Policy};
+use pool::BlockingPool;
+use ratelimit::RateLimits;
+use serenity::builder::{Builder, CreateAutocompleteResponse, CreateInteractionResponse};
+use serenity::gateway::{ConnectionStage, ShardStageUpdateEvent};
+use serenity::http::Http;
 use serenity::model::application::{Command, Interaction};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
-use std::env;
+use session::Sessions;
+use state::State;
+use std::{
+    env,
+    io::{self, Write},
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use strength::Dictionaries;
+use tokio::sync::watch;
+use totp::Totp;
+use tracing::{error, info, info_span, warn, Instrument};
 
-struct Bot;
+struct Bot {
+    commands: Vec<Box<dyn commands::Command>>,
+    policy: Policy,
+    alerts: Arc<Alerts>,
+    audit: AuditLog,
+    state: State,
+}
+
+impl Bot {
+    fn command(&self, name: &str) -> Option<&dyn commands::Command> {
+        self.commands
+            .iter()
+            .find(|command| command.name() == name)
+            .map(Box::as_ref)
+    }
+
+    // Logs an unauthorized attempt, and queues it to alert the owners:
+    fn deny(&self, user: &User, guild_id: Option<GuildId>, channel_id: ChannelId, command: &str) {
+        let guild_id = guild_id.map(GuildId::get);
+        let channel_id = channel_id.get();
+        warn!(guild_id, channel_id, "Unauthorized user.");
+        self.alerts.report(Attempt {
+            user_id: user.id.get(),
+            user_name: user.name.clone(),
+            guild_id,
+            channel_id,
+            command: command.to_string(),
+        });
+    }
+}
+
+impl Bot {
+    // Tells an unauthorized user they can't do that. Returns the outcome, for the logs:
+    async fn refuse(&self, ctx: &Context, interaction: &Interaction, user: &User) -> &'static str {
+        let response = Response::text(format!(
+            "I am sorry {}, I'm afraid I can't do that.",
+            user.name
+        ));
+        match response
+            .send(ctx, (interaction.id(), interaction.token()))
+            .await
+        {
+            Ok(()) => "denied",
+            Err(why) => {
+                error!(%why, "Could not respond to interaction.");
+                "error"
+            }
+        }
+    }
+
+    // Serves an interaction. Returns its outcome, for the logs.
+    async fn handle(&self, ctx: &Context, interaction: &Interaction) -> &'static str {
+        let response = match interaction {
+            Interaction::Command(command) => {
+                let usr = &command.user;
+
+                if !self
+                    .policy
+                    .allows(&command.data.name, &Caller::from(command))
+                {
+                    self.deny(
+                        usr,
+                        command.guild_id,
+                        command.channel_id,
+                        &command.data.name,
+                    );
+                    return self.refuse(ctx, interaction, usr).await;
+                }
+                if let Some(wait) = self.state.limits.cooldown(usr.id.get(), &command.data.name) {
+                    let response = Response::text(format!(
+                        "Not so fast! You can use this command again in {} seconds.",
+                        wait.as_secs().max(1)
+                    ));
+                    return match response
+                        .send(ctx, (interaction.id(), interaction.token()))
+                        .await
+                    {
+                        Ok(()) => "limited",
+                        Err(why) => {
+                            error!(%why, "Could not respond to interaction.");
+                            "error"
+                        }
+                    };
+                }
+                match self.command(&command.data.name) {
+                    Some(cmd) => cmd.run(ctx, command, &self.state).await,
+                    None => return "unknown",
+                }
+            }
+            Interaction::Autocomplete(autocomplete) => {
+                let mut outcome = "ok";
+                let suggestions = match self.command(&autocomplete.data.name) {
+                    Some(cmd) if self.policy.allows(cmd.name(), &Caller::from(autocomplete)) => cmd
+                        .autocomplete(ctx, autocomplete, &self.state)
+                        .await
+                        .unwrap_or_else(|err| {
+                            error!(%err, "Could not autocomplete.");
+                            outcome = "error";
+                            CreateAutocompleteResponse::new()
+                        }),
+                    Some(_) => {
+                        outcome = "denied";
+                        CreateAutocompleteResponse::new()
+                    }
+                    None => return "unknown",
+                };
+
+                let builder = CreateInteractionResponse::Autocomplete(suggestions);
+                if let Err(why) = builder
+                    .execute(ctx, (interaction.id(), interaction.token()))
+                    .await
+                {
+                    error!(%why, "Could not respond to autocomplete request.");
+                    outcome = "error";
+                }
+                return outcome;
+            }
+            Interaction::Modal(modal) => {
+                // Modals are routed to the command that showed them, by their ID's prefix:
+                let name = modal.data.custom_id.split(':').next().unwrap_or_default();
+                match self.command(name) {
+                    Some(cmd) if self.policy.allows(cmd.name(), &Caller::from(modal)) => {
+                        cmd.submit(ctx, modal, &self.state).await
+                    }
+                    Some(cmd) => {
+                        self.deny(&modal.user, modal.guild_id, modal.channel_id, cmd.name());
+                        return self.refuse(ctx, interaction, &modal.user).await;
+                    }
+                    None => return "unknown",
+                }
+            }
+            _ => return "unknown",
+        };
+
+        // On error, let the user know something went wrong, but keep the details in the logs:
+        let mut outcome = "ok";
+        let response = response.unwrap_or_else(|err| {
+            error!(%err, "Could not process interaction.");
+            outcome = "error";
+            Response::text(err.user_message())
+        });
+
+        if let Err(why) = response
+            .send(ctx, (interaction.id(), interaction.token()))
+            .await
+        {
+            error!(%why, "Could not respond to interaction.");
+            outcome = "error";
+        }
+        outcome
+    }
+}
 
 #[serenity::async_trait]
 impl EventHandler for Bot {
-    // Process slash commands:
+    // Process slash commands (and the autocompletion requests and modals for them):
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
-            // Authentication: only serve my user ID.
-            let usr = &command.user;
-            let usr_id = usr.id.get();
-            let name = &usr.name;
-
-            if usr_id != 231844961878802442 {
-                let content = CreateInteractionResponseMessage::new()
-                    .content(format!("I am sorry {}, I'm afraid I can't do that.", name))
-                    .ephemeral(true);
-                let builder = CreateInteractionResponse::Message(content);
-                if let Err(why) = command.create_response(&ctx.http, builder).await {
-                    println!("Could not respond to slash command: {why}");
-                }
+        // Only log what identifies the interaction, never the values of its options (hints,
+        // secret words...):
+        let (kind, command, user) = match &interaction {
+            Interaction::Command(command) => ("command", command.data.name.as_str(), &command.user),
+            Interaction::Autocomplete(autocomplete) => (
+                "autocomplete",
+                autocomplete.data.name.as_str(),
+                &autocomplete.user,
+            ),
+            Interaction::Modal(modal) => (
+                "modal",
+                modal.data.custom_id.split(':').next().unwrap_or_default(),
+                &modal.user,
+            ),
+            _ => return,
+        };
+        // Names come from the client, so only known ones make it into the logs, the metrics and
+        // the audit log:
+        let command = self.command(command).map_or("unknown", |cmd| cmd.name());
+        let span = info_span!("interaction", kind, command, user_id = user.id.get());
 
-                return;
-            }
+        let start = Instant::now();
+        let outcome = self
+            .handle(&ctx, &interaction)
+            .instrument(span.clone())
+            .await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+        span.in_scope(|| info!(outcome, latency_ms, "Interaction handled."));
+        self.state
+            .metrics
+            .record_interaction(kind, command, outcome);
 
-            let cmd_response = match command.data.name.as_str() {
-                "pswd" => Some(commands::pswd::run(&command.data.options())),
-                "cracktime" => Some(commands::cracktime::run(&command.data.options())),
-                "license" => Some(commands::license::run()),
-                "code" => Some(commands::code::run()),
+        // Autocompletion requests fire on every keystroke; only audit what was actually sent:
+        if kind != "autocomplete" {
+            let hint = audit::hint(&interaction);
+            let location = match &interaction {
+                _ if outcome != "denied" => None,
+                Interaction::Command(command) => Some((command.guild_id, command.channel_id)),
+                Interaction::Modal(modal) => Some((modal.guild_id, modal.channel_id)),
                 _ => None,
-            };
-
-            if let Some(response) = cmd_response {
-                let content = CreateInteractionResponseMessage::new()
-                    .content(response)
-                    .ephemeral(true);
-                let builder = CreateInteractionResponse::Message(content);
-                if let Err(why) = command.create_response(&ctx.http, builder).await {
-                    println!("Could not respond to slash command: {why}");
-                }
+            }
+            .map(|(guild_id, channel_id)| (guild_id.map(GuildId::get), channel_id.get()));
+            if let Err(err) =
+                self.audit
+                    .record(user.id.get(), command, outcome, hint.as_deref(), location)
+            {
+                span.in_scope(|| error!(%err, "Could not write the audit log."));
             }
         }
     }
 
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        self.state
+            .metrics
+            .set_gateway_connected(event.new == ConnectionStage::Connected);
+    }
+
     async fn ready(&self, ctx: Context, ready: Ready) {
+        self.state.metrics.set_gateway_connected(true);
         match ready.user.discriminator {
-            Some(discriminator) => println!("{}#{discriminator:#?} is connected.", ready.user.name),
-            None => println!("{} is connected.", ready.user.name),
+            Some(discriminator) => info!("{}#{discriminator:#?} is connected.", ready.user.name),
+            None => info!("{} is connected.", ready.user.name),
         }
 
         // Register slash commands:
-        let commands = Command::set_global_commands(
+        let commands = match Command::set_global_commands(
             &ctx.http,
-            vec![
-                commands::pswd::register(),
-                commands::cracktime::register(),
-                commands::license::register(),
-                commands::code::register(),
-            ],
+            self.commands.iter().map(|cmd| cmd.register()).collect(),
         )
         .await
-        .unwrap();
+        {
+            Ok(commands) => commands,
+            Err(why) => {
+                error!(%why, "Could not register slash commands.");
+                return;
+            }
+        };
 
-        println!(
+        info!(
             "Registered the following commands: {:?}",
             commands
                 .into_iter()
@@ -97,16 +314,139 @@ impl EventHandler for Bot {
     }
 }
 
+// Waits between reconnection attempts, doubling after every failure:
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+// How long closing the shards may take when shutting down:
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() {
+    let config = config::load().expect("Could not load the configuration.");
+
+    // `verify-audit` checks the audit log instead of running the bot:
+    if env::args().nth(1).as_deref() == Some("verify-audit") {
+        match audit::verify(&config.audit) {
+            Ok(count) => println!("The audit log is intact ({count} entries)."),
+            Err(err) => {
+                eprintln!("The audit log failed verification: {err}");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `build-bloom` builds the Bloom filter of breached passwords instead of running the bot:
+    if env::args().nth(1).as_deref() == Some("build-bloom") {
+        match breaches::build_bloom(&config.breaches) {
+            Ok(count) => println!("Built the Bloom filter ({count} hashes)."),
+            Err(err) => {
+                eprintln!("Could not build the Bloom filter: {err}");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     let token = env::var("DISCORD_TOKEN").expect("Erroneous DISCORD_TOKEN provided.");
-    let mut client = Client::builder(&token, GatewayIntents::default())
-        .event_handler(Bot)
-        .await
-        .expect("Could not build the Discord client.");
+    logging::init(&config.logging);
+    let audit = AuditLog::open(config.audit).expect("Could not open the audit log.");
 
-    client
-        .start()
-        .await
-        .expect("The client crashed for some reason!");
+    let sessions = Arc::new(Sessions::new(config.sessions.idle_timeout()));
+    tokio::spawn(session::expire_idle(sessions.clone()));
+
+    let metrics = Arc::new(Metrics::default());
+    if let Some(addr) = config.metrics.listen {
+        tokio::spawn(monitor::serve(addr, metrics.clone()));
+    }
+
+    let alerts = Arc::new(Alerts::new(config.alerts));
+    tokio::spawn(alerts::deliver(alerts.clone(), Arc::new(Http::new(&token))));
+
+    let dictionaries =
+        Arc::new(Dictionaries::load(&config.strength).expect("Could not load the dictionaries."));
+    if dictionaries.small() {
+        warn!("The dictionaries of /strength are too small: it will overestimate passwords.");
+    }
+
+    let bot = Arc::new(Bot {
+        commands: commands::registry(),
+        policy: config.policy,
+        alerts,
+        audit,
+        state: State {
+            sessions: sessions.clone(),
+            metrics: metrics.clone(),
+            pool: Arc::new(BlockingPool::new(config.derivations.max_concurrent)),
+            limits: Arc::new(
+                RateLimits::load(config.rate_limits).expect("Could not load the rate limits."),
+            ),
+            totp: Arc::new(Totp::load(config.totp).expect("Could not load the TOTP store.")),
+            models: Arc::new(
+                Models::load(&config.cracktime).expect("Could not load the attacker models."),
+            ),
+            breaches: Arc::new(
+                Breaches::load(&config.breaches).expect("Could not load the breach corpus."),
+            ),
+            dictionaries: dictionaries.clone(),
+        },
+    });
+
+    let (stop, stopping) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown::requested().await;
+        info!("Shutting down.");
+        stop.send_replace(true);
+    });
+
+    // Supervise the gateway connection: restart the client whenever it fails, until asked to
+    // stop.
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let mut client = Client::builder(&token, GatewayIntents::default())
+            .event_handler_arc(bot.clone())
+            .await
+            .expect("Could not build the Discord client.");
+
+        // Closing the shards makes `start` return. But if no shard is running (e.g. while the
+        // gateway is unreachable) it never does, so give up on it after a grace period:
+        let shard_manager = client.shard_manager.clone();
+        let mut stop_requested = stopping.clone();
+        let closer = async move {
+            if stop_requested.wait_for(|&stop| stop).await.is_ok() {
+                shard_manager.shutdown_all().await;
+                tokio::time::sleep(SHUTDOWN_GRACE).await;
+            }
+        };
+
+        let started = Instant::now();
+        let result = tokio::select! {
+            result = client.start() => result,
+            _ = closer => Ok(()),
+        };
+        metrics.set_gateway_connected(false);
+        if *stopping.borrow() {
+            break;
+        }
+        match result {
+            Ok(()) => warn!("The gateway connection was closed."),
+            Err(why) => error!(%why, "The gateway connection failed."),
+        }
+
+        // A connection that lasted long enough was not part of a streak of failures:
+        if started.elapsed() > MAX_BACKOFF {
+            backoff = MIN_BACKOFF;
+        }
+        info!(backoff_secs = backoff.as_secs(), "Reconnecting.");
+        let mut stop_requested = stopping.clone();
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = stop_requested.wait_for(|&stop| stop) => break,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    // Don't leave key material behind, and don't lose the last log lines:
+    sessions.lock_all();
+    let _ = io::stdout().flush();
 }
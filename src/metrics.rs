@@ -0,0 +1,144 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+// Upper bounds (in seconds) of the derivation latency histogram's buckets:
+const DERIVATION_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; DERIVATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+/// Counters about what the bot has been doing, exposed in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    gateway_connected: AtomicBool,
+    // Keyed by (kind, command, outcome):
+    interactions: Mutex<BTreeMap<(&'static str, &'static str, &'static str), u64>>,
+    denials: AtomicU64,
+    errors: AtomicU64,
+    derivations: Mutex<Histogram>,
+}
+
+impl Metrics {
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn gateway_connected(&self) -> bool {
+        self.gateway_connected.load(Ordering::Relaxed)
+    }
+
+    /// Counts a handled interaction, by its outcome (see `Bot::handle`).
+    pub fn record_interaction(
+        &self,
+        kind: &'static str,
+        command: &'static str,
+        outcome: &'static str,
+    ) {
+        *self
+            .interactions
+            .lock()
+            .unwrap()
+            .entry((kind, command, outcome))
+            .or_default() += 1;
+        match outcome {
+            "denied" => self.denials.fetch_add(1, Ordering::Relaxed),
+            "error" => self.errors.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+    }
+
+    pub fn observe_derivation(&self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        let mut histogram = self.derivations.lock().unwrap();
+        for (bucket, &bound) in histogram.buckets.iter_mut().zip(&DERIVATION_BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += secs;
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str(
+            "# HELP mother_brain_gateway_connected Whether the Discord gateway is connected.\n",
+        );
+        out.push_str("# TYPE mother_brain_gateway_connected gauge\n");
+        let connected = self.gateway_connected() as u8;
+        let _ = writeln!(out, "mother_brain_gateway_connected {connected}");
+
+        out.push_str("# HELP mother_brain_interactions_total Interactions handled, by kind, command and outcome.\n");
+        out.push_str("# TYPE mother_brain_interactions_total counter\n");
+        for ((kind, command, outcome), count) in self.interactions.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "mother_brain_interactions_total{{kind=\"{kind}\",command=\"{command}\",outcome=\"{outcome}\"}} {count}"
+            );
+        }
+
+        out.push_str("# HELP mother_brain_authorization_denials_total Interactions refused to unauthorized users.\n");
+        out.push_str("# TYPE mother_brain_authorization_denials_total counter\n");
+        let denials = self.denials.load(Ordering::Relaxed);
+        let _ = writeln!(out, "mother_brain_authorization_denials_total {denials}");
+
+        out.push_str("# HELP mother_brain_errors_total Interactions that failed.\n");
+        out.push_str("# TYPE mother_brain_errors_total counter\n");
+        let errors = self.errors.load(Ordering::Relaxed);
+        let _ = writeln!(out, "mother_brain_errors_total {errors}");
+
+        out.push_str("# HELP mother_brain_derivation_seconds Time taken to derive a password.\n");
+        out.push_str("# TYPE mother_brain_derivation_seconds histogram\n");
+        let histogram = self.derivations.lock().unwrap();
+        for (count, bound) in histogram.buckets.iter().zip(DERIVATION_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "mother_brain_derivation_seconds_bucket{{le=\"{bound}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "mother_brain_derivation_seconds_bucket{{le=\"+Inf\"}} {}",
+            histogram.count
+        );
+        let _ = writeln!(out, "mother_brain_derivation_seconds_sum {}", histogram.sum);
+        let _ = writeln!(
+            out,
+            "mother_brain_derivation_seconds_count {}",
+            histogram.count
+        );
+
+        out
+    }
+}
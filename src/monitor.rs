@@ -0,0 +1,108 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::commands::pswd::{SECRET_FILE, WORDLIST};
+use crate::metrics::Metrics;
+use std::{fs, net::SocketAddr, sync::Arc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{error, info, warn};
+
+// Requests are tiny (a GET line and a few headers); anything longer is not for us:
+const MAX_REQUEST_LEN: usize = 8192;
+
+// Checks whether the bot can serve commands. Returns whether it can, and a report of every check.
+fn health(metrics: &Metrics) -> (bool, String) {
+    let gateway = metrics.gateway_connected();
+    let wordlist = fs::read_to_string(WORDLIST).is_ok_and(|words| words.lines().next().is_some());
+    let secret = fs::metadata(SECRET_FILE).is_ok_and(|secret| secret.len() > 0);
+
+    let status = |ok| if ok { "ok" } else { "failing" };
+    let report = format!(
+        "gateway: {}\nwordlist: {}\nsecret: {}\n",
+        status(gateway),
+        status(wordlist),
+        status(secret)
+    );
+    (gateway && wordlist && secret, report)
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_LEN {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/healthz")) => match health(metrics) {
+            (true, report) => ("200 OK", "text/plain", report),
+            (false, report) => ("503 Service Unavailable", "text/plain", report),
+        },
+        (Some("GET"), Some("/metrics")) => {
+            ("200 OK", "text/plain; version=0.0.4", metrics.render())
+        }
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found.\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Only GET is supported.\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Serves `/healthz` and `/metrics` over HTTP on the given address, until the process exits.
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(why) => {
+            error!(%why, %addr, "Could not listen for health and metrics requests.");
+            return;
+        }
+    };
+    info!(%addr, "Serving health and metrics.");
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(why) => {
+                warn!(%why, "Could not accept a health or metrics request.");
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(why) = respond(stream, &metrics).await {
+                warn!(%why, "Could not answer a health or metrics request.");
+            }
+        });
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use serde::Deserialize;
+use serenity::model::application::{CommandInteraction, ModalInteraction};
+use serenity::model::id::{GuildId, RoleId};
+use std::collections::HashMap;
+
+/// Who can use a command, and where.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Rule {
+    /// Allow anyone (still limited by `guilds` and `dms`).
+    pub everyone: bool,
+    /// Allow these users.
+    pub users: Vec<u64>,
+    /// Allow the guild members with any of these roles.
+    pub roles: Vec<u64>,
+    /// Only allow the command in these guilds. Any guild, if empty.
+    pub guilds: Vec<u64>,
+    /// Allow the command in direct messages.
+    pub dms: bool,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            everyone: false,
+            users: Vec::new(),
+            roles: Vec::new(),
+            guilds: Vec::new(),
+            dms: true,
+        }
+    }
+}
+
+/// Who triggered an interaction, and where.
+pub struct Caller<'a> {
+    pub user_id: u64,
+    pub guild_id: Option<GuildId>,
+    pub roles: &'a [RoleId],
+}
+
+impl<'a> From<&'a CommandInteraction> for Caller<'a> {
+    fn from(interaction: &'a CommandInteraction) -> Self {
+        Self {
+            user_id: interaction.user.id.get(),
+            guild_id: interaction.guild_id,
+            roles: interaction
+                .member
+                .as_ref()
+                .map_or(&[], |member| &member.roles),
+        }
+    }
+}
+
+impl<'a> From<&'a ModalInteraction> for Caller<'a> {
+    fn from(interaction: &'a ModalInteraction) -> Self {
+        Self {
+            user_id: interaction.user.id.get(),
+            guild_id: interaction.guild_id,
+            roles: interaction
+                .member
+                .as_ref()
+                .map_or(&[], |member| &member.roles),
+        }
+    }
+}
+
+impl Rule {
+    fn allows(&self, caller: &Caller) -> bool {
+        let place = match caller.guild_id {
+            Some(guild_id) => self.guilds.is_empty() || self.guilds.contains(&guild_id.get()),
+            None => self.dms,
+        };
+        let person = self.everyone
+            || self.users.contains(&caller.user_id)
+            || caller
+                .roles
+                .iter()
+                .any(|role| self.roles.contains(&role.get()));
+        place && person
+    }
+}
+
+/// Authorization policy: a rule for every command, and a default one for the rest.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    pub default: Rule,
+    pub commands: HashMap<String, Rule>,
+}
+
+impl Default for Policy {
+    // Only serve my user ID:
+    fn default() -> Self {
+        Self {
+            default: Rule {
+                users: vec![231844961878802442],
+                ..Rule::default()
+            },
+            commands: HashMap::new(),
+        }
+    }
+}
+
+impl Policy {
+    /// Whether the caller may use the command.
+    pub fn allows(&self, command: &str, caller: &Caller) -> bool {
+        self.commands
+            .get(command)
+            .unwrap_or(&self.default)
+            .allows(caller)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: u64 = 231844961878802442;
+    const GUILD: u64 = 1000;
+    const ROLE: u64 = 2000;
+
+    fn in_guild(user_id: u64, guild_id: u64, roles: &[RoleId]) -> Caller<'_> {
+        Caller {
+            user_id,
+            guild_id: Some(GuildId::new(guild_id)),
+            roles,
+        }
+    }
+
+    fn in_dms(user_id: u64) -> Caller<'static> {
+        Caller {
+            user_id,
+            guild_id: None,
+            roles: &[],
+        }
+    }
+
+    fn policy(default: Rule) -> Policy {
+        Policy {
+            default,
+            commands: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn allows_listed_users() {
+        let policy = policy(Rule {
+            users: vec![1],
+            ..Rule::default()
+        });
+        assert!(policy.allows("pswd", &in_guild(1, GUILD, &[])));
+        assert!(policy.allows("pswd", &in_dms(1)));
+        assert!(!policy.allows("pswd", &in_guild(2, GUILD, &[])));
+    }
+
+    #[test]
+    fn allows_listed_roles() {
+        let policy = policy(Rule {
+            roles: vec![ROLE],
+            ..Rule::default()
+        });
+        assert!(policy.allows("pswd", &in_guild(2, GUILD, &[RoleId::new(ROLE)])));
+        assert!(!policy.allows("pswd", &in_guild(2, GUILD, &[RoleId::new(ROLE + 1)])));
+        assert!(!policy.allows("pswd", &in_dms(2)));
+    }
+
+    #[test]
+    fn allows_everyone() {
+        let policy = policy(Rule {
+            everyone: true,
+            ..Rule::default()
+        });
+        assert!(policy.allows("pswd", &in_guild(3, GUILD, &[])));
+        assert!(policy.allows("pswd", &in_dms(3)));
+    }
+
+    #[test]
+    fn only_allows_listed_guilds() {
+        let policy = policy(Rule {
+            everyone: true,
+            guilds: vec![GUILD],
+            ..Rule::default()
+        });
+        assert!(policy.allows("pswd", &in_guild(1, GUILD, &[])));
+        assert!(!policy.allows("pswd", &in_guild(1, GUILD + 1, &[])));
+    }
+
+    #[test]
+    fn rejects_dms_if_disabled() {
+        let policy = policy(Rule {
+            everyone: true,
+            dms: false,
+            ..Rule::default()
+        });
+        assert!(policy.allows("pswd", &in_guild(1, GUILD, &[])));
+        assert!(!policy.allows("pswd", &in_dms(1)));
+    }
+
+    #[test]
+    fn command_rules_override_the_default() {
+        let mut policy = policy(Rule {
+            users: vec![1],
+            ..Rule::default()
+        });
+        policy.commands.insert(
+            "cracktime".to_string(),
+            Rule {
+                everyone: true,
+                ..Rule::default()
+            },
+        );
+        policy.commands.insert(
+            "pswd".to_string(),
+            Rule {
+                users: vec![2],
+                ..Rule::default()
+            },
+        );
+        assert!(policy.allows("cracktime", &in_dms(3)));
+        assert!(policy.allows("pswd", &in_dms(2)));
+        assert!(!policy.allows("pswd", &in_dms(1)));
+        assert!(policy.allows("totp", &in_dms(1)));
+    }
+
+    #[test]
+    fn default_policy_only_allows_the_owner() {
+        let policy = Policy::default();
+        assert!(policy.allows("pswd", &in_dms(OWNER)));
+        assert!(policy.allows("pswd", &in_guild(OWNER, GUILD, &[])));
+        assert!(!policy.allows("pswd", &in_dms(OWNER + 1)));
+        assert!(!policy.allows("pswd", &in_guild(1, GUILD, &[RoleId::new(ROLE)])));
+    }
+}
@@ -0,0 +1,332 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::config::RateLimitsConfig;
+use crate::duration::unix_time;
+use crate::error::{Error, Result};
+use crate::storage::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::read,
+    io::{ErrorKind, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Bucket {
+    tokens: f64,
+    // Seconds since the Unix epoch (so buckets survive restarts):
+    updated: f64,
+    // Times the bucket ran dry since it was last full:
+    strikes: u32,
+    locked_until: f64,
+}
+
+impl Bucket {
+    fn full(capacity: f64, now: f64) -> Self {
+        Self {
+            tokens: capacity,
+            updated: now,
+            strikes: 0,
+            locked_until: 0.0,
+        }
+    }
+
+    fn refill(&mut self, capacity: f64, per_second: f64, now: f64) {
+        self.tokens = (self.tokens + (now - self.updated).max(0.0) * per_second).min(capacity);
+        self.updated = now;
+        // Only forgive past strikes once the bucket has had time to fill up again:
+        if self.tokens >= capacity && now >= self.locked_until {
+            self.strikes = 0;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Buckets {
+    global: Option<Bucket>,
+    users: HashMap<u64, Bucket>,
+}
+
+fn now() -> f64 {
+    unix_time().as_secs_f64()
+}
+
+/// Limits how often secret words can be tried (and passwords derived): token buckets per user
+/// and for everyone together, locking out for exponentially longer the users that keep
+/// draining theirs. The buckets are saved to disk, so restarting the bot doesn't reset them.
+/// Also enforces the per-command cooldowns.
+pub struct RateLimits {
+    config: RateLimitsConfig,
+    buckets: Mutex<Buckets>,
+    last_used: Mutex<HashMap<(u64, String), Instant>>,
+}
+
+impl RateLimits {
+    /// Loads the buckets saved in the configured state file, if any.
+    pub fn load(config: RateLimitsConfig) -> Result<Self> {
+        // Rates of 0 mean attempts are never given back, but at least one must be possible:
+        let limits = [
+            ("user_burst", config.user_burst, 1.0),
+            ("global_burst", config.global_burst, 1.0),
+            ("user_per_minute", config.user_per_minute, 0.0),
+            ("global_per_minute", config.global_per_minute, 0.0),
+        ];
+        for (name, value, min) in limits {
+            if !(value >= min && value.is_finite()) {
+                return Err(Error::InvalidRateLimits(format!(
+                    "{name} must be at least {min}"
+                )));
+            }
+        }
+
+        let buckets = match read(&config.state_file) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Buckets::default(),
+            Err(err) => return Err(Error::io("Could not read the rate limits file")(err)),
+        };
+        Ok(Self {
+            config,
+            buckets: Mutex::new(buckets),
+            last_used: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Takes a token for an attempt of the user. Returns how long to wait instead, if the
+    /// attempt is refused.
+    pub fn attempt(&self, user_id: u64) -> Result<Option<Duration>> {
+        self.attempt_at(user_id, now())
+    }
+
+    fn attempt_at(&self, user_id: u64, now: f64) -> Result<Option<Duration>> {
+        let config = &self.config;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let user_rate = config.user_per_minute / 60.0;
+        let global_rate = config.global_per_minute / 60.0;
+        let mut user = buckets
+            .users
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_else(|| Bucket::full(config.user_burst, now));
+        user.refill(config.user_burst, user_rate, now);
+        let mut global = buckets
+            .global
+            .clone()
+            .unwrap_or_else(|| Bucket::full(config.global_burst, now));
+        global.refill(config.global_burst, global_rate, now);
+
+        let wait = if now < user.locked_until {
+            Some(user.locked_until - now)
+        } else if user.tokens < 1.0 {
+            // Every time the user runs out of tokens, lock them out for twice as long:
+            user.strikes += 1;
+            let lockout = (config.lockout_base_secs as f64 * 2f64.powi(user.strikes as i32 - 1))
+                .min(config.lockout_max_secs as f64);
+            user.locked_until = now + lockout;
+            Some(lockout)
+        } else if global.tokens < 1.0 {
+            Some((1.0 - global.tokens) / global_rate)
+        } else {
+            user.tokens -= 1.0;
+            global.tokens -= 1.0;
+            None
+        };
+
+        // Users with a full bucket and a clean record are as good as new; don't keep them:
+        if user.tokens >= config.user_burst && user.strikes == 0 && now >= user.locked_until {
+            buckets.users.remove(&user_id);
+        } else {
+            buckets.users.insert(user_id, user);
+        }
+        buckets.global = Some(global);
+        self.save(&buckets)?;
+
+        // Without refills, the global bucket would never let anyone in again. Never ask to wait
+        // longer than the longest lockout anyway:
+        let max_wait = config.lockout_max_secs as f64;
+        Ok(wait.map(|wait| Duration::from_secs_f64(wait.clamp(0.0, max_wait))))
+    }
+
+    /// Starts the command's cooldown for the user. Returns how long is left of it instead, if
+    /// the user used the command too recently.
+    pub fn cooldown(&self, user_id: u64, command: &str) -> Option<Duration> {
+        let cooldown = Duration::from_secs(*self.config.cooldowns.get(command)?);
+        let mut last_used = self.last_used.lock().unwrap();
+        let now = Instant::now();
+        last_used.retain(|(_, command), used| {
+            now.duration_since(*used).as_secs() < self.config.cooldowns[command]
+        });
+
+        match last_used.get(&(user_id, command.to_string())) {
+            Some(used) => Some(cooldown.saturating_sub(now.duration_since(*used))),
+            None => {
+                last_used.insert((user_id, command.to_string()), now);
+                None
+            }
+        }
+    }
+
+    fn save(&self, buckets: &Buckets) -> Result<()> {
+        let data = serde_json::to_vec(buckets)?;
+        write_atomically(
+            &self.config.state_file,
+            "Could not write the rate limits file",
+            |file| file.write_all(&data),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_file};
+
+    const START: f64 = 1e9;
+
+    // Limits of 3 attempts per user (one back per minute) and 100 for everyone, locking out for
+    // 60 seconds at first and 200 at most, saved in a file of their own:
+    fn config(name: &str) -> RateLimitsConfig {
+        let dir = std::env::temp_dir().join("mother-brain-ratelimits");
+        create_dir_all(&dir).unwrap();
+        let state_file = dir.join(format!("{name}.json"));
+        let _ = remove_file(&state_file);
+        RateLimitsConfig {
+            user_burst: 3.0,
+            user_per_minute: 1.0,
+            global_burst: 100.0,
+            global_per_minute: 100.0,
+            lockout_base_secs: 60,
+            lockout_max_secs: 200,
+            state_file: state_file.to_string_lossy().into_owned(),
+            cooldowns: HashMap::new(),
+        }
+    }
+
+    // Makes attempts at the given time until one is refused, returning the wait:
+    fn drain(limits: &RateLimits, user_id: u64, now: f64) -> f64 {
+        loop {
+            if let Some(wait) = limits.attempt_at(user_id, now).unwrap() {
+                return wait.as_secs_f64();
+            }
+        }
+    }
+
+    #[test]
+    fn burst_is_allowed_then_locked_out() {
+        let limits = RateLimits::load(config("burst")).unwrap();
+        for _ in 0..3 {
+            assert!(limits.attempt_at(1, START).unwrap().is_none());
+        }
+        assert_eq!(
+            limits.attempt_at(1, START).unwrap(),
+            Some(Duration::from_secs(60))
+        );
+        // Still locked out a bit later, for the rest of the lockout:
+        assert_eq!(
+            limits.attempt_at(1, START + 20.0).unwrap(),
+            Some(Duration::from_secs(40))
+        );
+    }
+
+    #[test]
+    fn lockouts_double_up_to_the_max() {
+        let limits = RateLimits::load(config("doubling")).unwrap();
+        let mut now = START;
+        let mut lockouts = Vec::new();
+        for _ in 0..3 {
+            let lockout = drain(&limits, 1, now);
+            lockouts.push(lockout);
+            now += lockout;
+        }
+        assert_eq!(lockouts, [60.0, 120.0, 200.0]);
+    }
+
+    #[test]
+    fn full_refill_clears_strikes() {
+        let limits = RateLimits::load(config("refill")).unwrap();
+        assert_eq!(drain(&limits, 1, START), 60.0);
+        // Three minutes later, all three attempts are back:
+        let now = START + 180.0;
+        assert_eq!(drain(&limits, 1, now), 60.0);
+    }
+
+    #[test]
+    fn buckets_survive_restarts() {
+        let config = config("restart");
+        let limits = RateLimits::load(config.clone()).unwrap();
+        assert_eq!(drain(&limits, 1, START), 60.0);
+
+        let limits = RateLimits::load(config).unwrap();
+        assert_eq!(
+            limits.attempt_at(1, START + 30.0).unwrap(),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn global_bucket_blocks_everyone() {
+        let limits = RateLimits::load(RateLimitsConfig {
+            global_burst: 2.0,
+            ..config("global")
+        })
+        .unwrap();
+        assert!(limits.attempt_at(1, START).unwrap().is_none());
+        assert!(limits.attempt_at(2, START).unwrap().is_none());
+        // Another user has to wait for the global bucket to refill (100 a minute):
+        let wait = limits.attempt_at(3, START).unwrap().unwrap();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn global_bucket_without_refills_waits_the_longest_lockout() {
+        let limits = RateLimits::load(RateLimitsConfig {
+            global_burst: 1.0,
+            global_per_minute: 0.0,
+            ..config("no-refill")
+        })
+        .unwrap();
+        assert!(limits.attempt_at(1, START).unwrap().is_none());
+        assert_eq!(
+            limits.attempt_at(2, START).unwrap(),
+            Some(Duration::from_secs(200))
+        );
+    }
+
+    #[test]
+    fn nonsensical_limits_are_rejected() {
+        for config in [
+            RateLimitsConfig {
+                global_per_minute: -1.0,
+                ..config("negative")
+            },
+            RateLimitsConfig {
+                user_burst: 0.0,
+                ..config("empty")
+            },
+            RateLimitsConfig {
+                user_per_minute: f64::NAN,
+                ..config("nan")
+            },
+        ] {
+            assert!(RateLimits::load(config).is_err());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use zeroize::Zeroizing;
+
+struct Session {
+    pswd_key: Zeroizing<Vec<u8>>,
+    last_used: Instant,
+}
+
+/// Password keys of the users that have unlocked a session, so they don't need to type their
+/// secret words for every command. Keys are zeroized when their session is locked or expires.
+pub struct Sessions {
+    idle_timeout: Duration,
+    sessions: Mutex<HashMap<u64, Session>>,
+}
+
+impl Sessions {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    pub fn unlock(&self, user_id: u64, pswd_key: Vec<u8>) {
+        let session = Session {
+            pswd_key: Zeroizing::new(pswd_key),
+            last_used: Instant::now(),
+        };
+        self.sessions.lock().unwrap().insert(user_id, session);
+    }
+
+    /// Locks the user's session. Returns whether there was a session to lock.
+    pub fn lock(&self, user_id: u64) -> bool {
+        self.sessions.lock().unwrap().remove(&user_id).is_some()
+    }
+
+    /// Returns the password key of the user's session (if still live), restarting its idle
+    /// timeout.
+    pub fn key(&self, user_id: u64) -> Option<Zeroizing<Vec<u8>>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(&user_id)?;
+        if session.last_used.elapsed() >= self.idle_timeout {
+            sessions.remove(&user_id);
+            return None;
+        }
+
+        session.last_used = Instant::now();
+        Some(session.pswd_key.clone())
+    }
+
+    /// Locks the sessions that have been idle for too long.
+    pub fn expire(&self) {
+        let idle_timeout = self.idle_timeout;
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session.last_used.elapsed() < idle_timeout);
+    }
+
+    /// Locks every session (e.g. before shutting down).
+    pub fn lock_all(&self) {
+        self.sessions.lock().unwrap().clear();
+    }
+}
+
+/// Periodically locks idle sessions, so their keys don't linger in memory until next used.
+pub async fn expire_idle(sessions: Arc<Sessions>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        interval.tick().await;
+        sessions.expire();
+    }
+}
@@ -0,0 +1,39 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::attackers::Models;
+use crate::breaches::Breaches;
+use crate::metrics::Metrics;
+use crate::pool::BlockingPool;
+use crate::ratelimit::RateLimits;
+use crate::session::Sessions;
+use crate::strength::Dictionaries;
+use crate::totp::Totp;
+use std::sync::Arc;
+
+/// Everything the commands share while the bot runs.
+pub struct State {
+    pub sessions: Arc<Sessions>,
+    pub metrics: Arc<Metrics>,
+    pub pool: Arc<BlockingPool>,
+    pub limits: Arc<RateLimits>,
+    pub totp: Arc<Totp>,
+    pub models: Arc<Models>,
+    pub breaches: Arc<Breaches>,
+    pub dictionaries: Arc<Dictionaries>,
+}
@@ -0,0 +1,45 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::error::{Error, Result};
+use std::{
+    ffi::OsString,
+    fs::{rename, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Replaces the file with what `write` writes to it. It's written to a temporary file first,
+/// so a crash never leaves a half-written file behind. `context` describes the file for errors
+/// (e.g. "Could not write the TOTP store").
+pub fn write_atomically(
+    path: impl AsRef<Path>,
+    context: &str,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> Result<()> {
+    let path = path.as_ref();
+    let mut tmp_path = OsString::from(path);
+    tmp_path.push(".tmp");
+
+    let mut file = BufWriter::new(File::create(&tmp_path).map_err(Error::io(context))?);
+    write(&mut file)
+        .and_then(|()| file.flush())
+        .map_err(Error::io(context))?;
+    drop(file);
+    rename(&tmp_path, path).map_err(Error::io(context))
+}
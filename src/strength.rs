@@ -0,0 +1,823 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::commands::pswd::WORDLIST;
+use crate::config::StrengthConfig;
+use crate::error::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use zeroize::Zeroizing;
+
+// Dictionaries shipped with the bot, most common entries first. They're only a fallback, too
+// small to catch most common passwords (see data/fetch-dictionaries.sh for real ones):
+const COMMON_PASSWORDS: &str = include_str!("../data/common_passwords.txt");
+const ENGLISH_WORDS: &str = include_str!("../data/english.txt");
+// Ranked dictionaries smaller than this make for estimates too optimistic to trust:
+const MIN_RANKED_WORDS: usize = 10000;
+
+// The rest of the figures follow zxcvbn's, which this estimator is modelled after:
+//  * https://www.usenix.org/conference/usenixsecurity16/technical-sessions/presentation/wheeler
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_GUESSES_MULTI_CHAR: f64 = 50.0;
+// Every additional pattern in a password costs the attacker this many guesses at least:
+const MIN_GUESSES_PER_PATTERN: f64 = 10000.0;
+// Years around the current one are all about equally likely in dates:
+const MIN_YEAR_SPACE: f64 = 20.0;
+const KEYBOARD_STARTING_KEYS: f64 = 94.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.6;
+
+// Common l33t substitutions, with the letters they can stand for:
+const L33T: [(char, &str); 20] = [
+    ('4', "a"),
+    ('@', "a"),
+    ('8', "b"),
+    ('(', "c"),
+    ('{', "c"),
+    ('[', "c"),
+    ('<', "c"),
+    ('3', "e"),
+    ('6', "g"),
+    ('9', "g"),
+    ('1', "il"),
+    ('!', "i"),
+    ('|', "il"),
+    ('7', "lt"),
+    ('0', "o"),
+    ('$', "s"),
+    ('5', "s"),
+    ('+', "t"),
+    ('%', "x"),
+    ('2', "z"),
+];
+// At most this many combinations of ambiguous substitutions are tried:
+const MAX_L33T_TABLES: usize = 32;
+
+// A QWERTY keyboard, row by row, without and with shift:
+const KEYBOARD: [&str; 4] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+const KEYBOARD_SHIFTED: [&str; 4] = [
+    "~!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+];
+
+const DATE_SEPARATORS: [char; 6] = [' ', '-', '/', '\\', '_', '.'];
+
+/// A pattern attackers would try to guess part of a password with.
+pub enum Pattern {
+    /// A word from one of the dictionaries, ranked by how common it is.
+    Dictionary {
+        dictionary: &'static str,
+        /// None for dictionaries whose words are all equally likely.
+        rank: Option<usize>,
+        reversed: bool,
+        l33t: bool,
+        capitals: bool,
+    },
+    /// Keys next to each other on the keyboard.
+    Keyboard { turns: usize },
+    /// A day, month and year.
+    Date,
+    /// A year alone.
+    Year,
+    /// Some characters repeated several times.
+    Repeat { times: usize },
+    /// Characters at constant steps (e.g. "abc" or "9753").
+    Sequence,
+    /// Anything else, that can only be guessed by brute force.
+    Bruteforce,
+}
+
+/// A part of a password, with the pattern it was found to follow.
+pub struct Match {
+    /// Position of the part, in characters.
+    pub start: usize,
+    pub end: usize,
+    pub pattern: Pattern,
+    /// Guesses needed to find the part.
+    pub guesses: f64,
+}
+
+/// How a password would most likely be guessed.
+pub struct Analysis {
+    /// Guesses needed to find the whole password.
+    pub guesses: f64,
+    /// The parts it is made of, in order.
+    pub matches: Vec<Match>,
+}
+
+/// The word lists passwords are matched against.
+pub struct Dictionaries {
+    // Name, rank of every word, and whether the ranks mean anything:
+    lists: Vec<(&'static str, HashMap<String, usize>, bool)>,
+    longest: usize,
+}
+
+impl Dictionaries {
+    /// Loads the configured dictionaries of common passwords and English words (or else, the
+    /// ones shipped with the bot), plus the word list passwords are generated from.
+    pub fn load(config: &StrengthConfig) -> Result<Self> {
+        let read = |file: &Option<String>, shipped: &str| match file {
+            Some(file) => read_to_string(file)
+                .map_err(Error::io(format!("Could not read the dictionary {file}"))),
+            None => Ok(shipped.to_string()),
+        };
+        let passwords = read(&config.passwords_file, COMMON_PASSWORDS)?;
+        let words = read(&config.words_file, ENGLISH_WORDS)?;
+        let wordlist =
+            read_to_string(WORDLIST).map_err(Error::io("Could not open word-list file"))?;
+        Ok(Self::new(&passwords, &words, &wordlist))
+    }
+
+    /// The given dictionaries of common passwords and English words, most common entries
+    /// first, plus the given word list (the one passwords are generated from), whose words are
+    /// all equally likely.
+    pub fn new(passwords: &str, words: &str, wordlist: &str) -> Self {
+        let ranked = |list: &str| -> HashMap<String, usize> {
+            let mut ranks = HashMap::new();
+            for (i, word) in list.lines().enumerate() {
+                ranks.entry(word.trim().to_lowercase()).or_insert(i + 1);
+            }
+            ranks
+        };
+        let generated: HashSet<String> = wordlist
+            .lines()
+            .map(|word| word.trim().to_lowercase())
+            .collect();
+        let size = generated.len();
+        let lists = vec![
+            ("common passwords", ranked(passwords), true),
+            ("common English words", ranked(words), true),
+            (
+                "word list",
+                generated.into_iter().map(|word| (word, size)).collect(),
+                false,
+            ),
+        ];
+        let longest = (lists.iter())
+            .flat_map(|(_, ranks, _)| ranks.keys())
+            .map(|word| word.chars().count())
+            .max()
+            .unwrap_or(0);
+        Self { lists, longest }
+    }
+
+    /// Whether some dictionary of common passwords or words is too small to trust the
+    /// estimates (as the ones shipped with the bot are).
+    pub fn small(&self) -> bool {
+        (self.lists.iter()).any(|(_, ranks, ranked)| *ranked && ranks.len() < MIN_RANKED_WORDS)
+    }
+
+    // The most common rank of the word, in any of the dictionaries:
+    fn rank(&self, word: &str) -> Option<(&'static str, usize, bool)> {
+        (self.lists.iter())
+            .filter_map(|(name, ranks, ranked)| Some((*name, *ranks.get(word)?, *ranked)))
+            .min_by_key(|(_, rank, _)| *rank)
+    }
+}
+
+impl Analysis {
+    /// The password's entropy, in bits.
+    pub fn bits(&self) -> f64 {
+        self.guesses.log2()
+    }
+}
+
+/// Estimates how many guesses an attacker would need to find the password, by splitting it
+/// into the parts most easily guessed: dictionary words (also reversed, capitalized or with
+/// l33t substitutions), keyboard walks, dates, repeats and sequences.
+pub fn analyze(password: &str, dictionaries: &Dictionaries, this_year: i32) -> Analysis {
+    let chars = Zeroizing::new(password.chars().collect::<Vec<char>>());
+    analyze_chars(&chars, dictionaries, this_year)
+}
+
+fn analyze_chars(chars: &[char], dictionaries: &Dictionaries, this_year: i32) -> Analysis {
+    let mut matches = Vec::new();
+    matches.extend(dictionary_matches(chars, dictionaries, false));
+    matches.extend(dictionary_matches(chars, dictionaries, true));
+    matches.extend(keyboard_matches(chars));
+    matches.extend(date_matches(chars, this_year));
+    matches.extend(repeat_matches(chars, dictionaries, this_year));
+    matches.extend(sequence_matches(chars));
+    for m in &mut matches {
+        m.guesses = m.guesses.max(min_guesses(m.end - m.start, chars.len()));
+    }
+    most_guessable(chars.len(), matches)
+}
+
+// Parts of a password are never taken as easier to guess than this:
+fn min_guesses(len: usize, password_len: usize) -> f64 {
+    match len {
+        _ if len == password_len => 1.0,
+        1 => MIN_GUESSES_SINGLE_CHAR,
+        _ => MIN_GUESSES_MULTI_CHAR,
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |result, i| result * (n - i) as f64 / (i + 1) as f64)
+}
+
+// Ways to mix two kinds of characters, as many of each as given, with at least one of the
+// first kind (e.g. ways to capitalize some letters of a word):
+fn variations(kind: usize, others: usize) -> f64 {
+    (1..=kind.min(others))
+        .map(|i| binomial(kind + others, i))
+        .sum()
+}
+
+// Guesses to also try the ways the word could be capitalized:
+fn capitalizations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+    let first_only = upper == 1 && word[0].is_uppercase();
+    let last_only = upper == 1 && word[word.len() - 1].is_uppercase();
+    match upper {
+        0 => 1.0,
+        _ if lower == 0 || first_only || last_only => 2.0,
+        _ => variations(upper, lower),
+    }
+}
+
+// Every way to undo the l33t substitutions in the password (trying all the letters ambiguous
+// characters could stand for, or none), as tables from character to letter:
+fn l33t_tables(chars: &[char]) -> Vec<HashMap<char, char>> {
+    let mut tables = vec![HashMap::new()];
+    for (c, letters) in L33T {
+        if !chars.contains(&c) {
+            continue;
+        }
+        let mut extended = Vec::new();
+        for table in &tables {
+            // The character may also be meant as itself:
+            extended.push(table.clone());
+            for letter in letters.chars() {
+                let mut table = table.clone();
+                table.insert(c, letter);
+                extended.push(table);
+            }
+        }
+        extended.truncate(MAX_L33T_TABLES);
+        tables = extended;
+    }
+    tables
+}
+
+fn dictionary_matches(chars: &[char], dictionaries: &Dictionaries, reversed: bool) -> Vec<Match> {
+    let n = chars.len();
+    let mut original = Zeroizing::new(chars.to_vec());
+    if reversed {
+        original.reverse();
+    }
+    let lower = Zeroizing::new(
+        (original.iter())
+            .map(|c| c.to_lowercase().next().unwrap_or(*c))
+            .collect::<Vec<char>>(),
+    );
+
+    let mut matches = Vec::new();
+    let mut word = Zeroizing::new(String::new());
+    let mut subs = Vec::new();
+    for table in l33t_tables(&lower) {
+        let unsubbed = Zeroizing::new(
+            (lower.iter())
+                .map(|c| *table.get(c).unwrap_or(c))
+                .collect::<Vec<char>>(),
+        );
+        for i in 0..n {
+            word.clear();
+            for j in i..n.min(i + dictionaries.longest) {
+                word.push(unsubbed[j]);
+                let Some((dictionary, rank, ranked)) = dictionaries.rank(&word) else {
+                    continue;
+                };
+
+                // Words without substitutions are found with the empty table already:
+                subs.clear();
+                for k in i..=j {
+                    if lower[k] != unsubbed[k] && !subs.contains(&(lower[k], unsubbed[k])) {
+                        subs.push((lower[k], unsubbed[k]));
+                    }
+                }
+                if !table.is_empty() && subs.is_empty() {
+                    continue;
+                }
+
+                // Guessing which characters were substituted:
+                let token = &lower[i..=j];
+                let l33t: f64 = (subs.iter())
+                    .map(|(subbed, letter)| {
+                        let subbed = token.iter().filter(|c| *c == subbed).count();
+                        let unsubbed = token.iter().filter(|c| *c == letter).count();
+                        match unsubbed {
+                            0 => 2.0,
+                            _ => variations(subbed, unsubbed),
+                        }
+                    })
+                    .product();
+                let capitals = capitalizations(&original[i..=j]);
+                let guesses = rank as f64 * capitals * l33t * if reversed { 2.0 } else { 1.0 };
+
+                let (start, end) = match reversed {
+                    true => (n - 1 - j, n - i),
+                    false => (i, j + 1),
+                };
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Dictionary {
+                        dictionary,
+                        rank: ranked.then_some(rank),
+                        reversed,
+                        l33t: !subs.is_empty(),
+                        capitals: capitals > 1.0,
+                    },
+                    guesses,
+                });
+            }
+        }
+    }
+    matches
+}
+
+// The row and column of a key on the keyboard, and whether it needs shift:
+fn key(c: char) -> Option<(i32, i32, bool)> {
+    for (row, (keys, shifted)) in KEYBOARD.iter().zip(KEYBOARD_SHIFTED).enumerate() {
+        if let Some(col) = keys.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, false));
+        }
+        if let Some(col) = shifted.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, true));
+        }
+    }
+    None
+}
+
+// The direction from one key to the next, if they are next to each other. Rows are staggered,
+// so each key touches two keys of the row above, and two of the row below:
+fn direction(from: char, to: char) -> Option<(i32, i32)> {
+    let (row, col, _) = key(from)?;
+    let (next_row, next_col, _) = key(to)?;
+    let step = (next_row - row, next_col - col);
+    match step {
+        (0, -1) | (0, 1) | (-1, 0) | (-1, 1) | (1, -1) | (1, 0) => Some(step),
+        _ => None,
+    }
+}
+
+fn keyboard_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut j = i;
+        let mut turns = 0;
+        let mut last = None;
+        while j + 1 < chars.len() {
+            let Some(step) = direction(chars[j], chars[j + 1]) else {
+                break;
+            };
+            if last != Some(step) {
+                turns += 1;
+                last = Some(step);
+            }
+            j += 1;
+        }
+
+        let len = j - i + 1;
+        if len >= 3 {
+            // Every walk of up to this length and number of turns, from every key:
+            let mut guesses = 0.0;
+            for walk in 2..=len {
+                for turn in 1..=turns.min(walk - 1) {
+                    guesses += binomial(walk - 1, turn - 1)
+                        * KEYBOARD_STARTING_KEYS
+                        * KEYBOARD_AVERAGE_DEGREE.powi(turn as i32);
+                }
+            }
+            let walk = &chars[i..=j];
+            let shifted = (walk.iter())
+                .filter(|c| key(**c).is_some_and(|(_, _, shifted)| shifted))
+                .count();
+            guesses *= match shifted {
+                0 => 1.0,
+                _ if shifted == len => 2.0,
+                _ => variations(shifted, len - shifted),
+            };
+            matches.push(Match {
+                start: i,
+                end: j + 1,
+                pattern: Pattern::Keyboard { turns },
+                guesses,
+            });
+        }
+        i = j.max(i + 1);
+    }
+    matches
+}
+
+fn number(digits: &[char]) -> Option<i32> {
+    digits.iter().collect::<String>().parse().ok()
+}
+
+// Takes two-digit years as the closest to 2000 (e.g. 87 as 1987, and 15 as 2015):
+fn full_year(year: i32, digits: usize) -> i32 {
+    match (digits, year) {
+        (2, 0..=50) => 2000 + year,
+        (2, _) => 1900 + year,
+        _ => year,
+    }
+}
+
+// The year of the date given as day, month and year (in any of the usual orders), if valid:
+fn date_year(parts: [&[char]; 3]) -> Option<i32> {
+    let [first, second, third] = parts.map(number);
+    let (first, second, third) = (first?, second?, third?);
+    let valid = |day: i32, month: i32, year: i32, digits: usize| {
+        let year = full_year(year, digits);
+        ((1..=31).contains(&day) && (1..=12).contains(&month) && (1000..=2050).contains(&year))
+            .then_some(year)
+    };
+    let (year_first, year_last) = (parts[0].len(), parts[2].len());
+    let candidates = [
+        (year_last != 1).then(|| valid(first, second, third, year_last)),
+        (year_last != 1).then(|| valid(second, first, third, year_last)),
+        (year_first != 1).then(|| valid(third, second, first, year_first)),
+    ];
+    candidates.into_iter().flatten().flatten().next()
+}
+
+fn date_guesses(year: i32, this_year: i32) -> f64 {
+    365.0 * (year - this_year).abs().max(MIN_YEAR_SPACE as i32) as f64
+}
+
+fn date_matches(chars: &[char], this_year: i32) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let n = chars.len();
+    for i in 0..n {
+        // Dates without separators (e.g. 1311987), split in every possible way:
+        for len in 4..=8.min(n - i) {
+            let digits = &chars[i..i + len];
+            if !digits.iter().all(char::is_ascii_digit) {
+                break;
+            }
+            if len == 4 {
+                if let Some(year) = number(digits).filter(|year| (1900..=2099).contains(year)) {
+                    matches.push(Match {
+                        start: i,
+                        end: i + len,
+                        pattern: Pattern::Year,
+                        guesses: (year - this_year).abs().max(MIN_YEAR_SPACE as i32) as f64,
+                    });
+                }
+            }
+            let splits = (1..len - 1).flat_map(|a| (a + 1..len).map(move |b| (a, b)));
+            let year = splits
+                .filter(|(a, b)| *a <= 4 && b - a <= 2 && len - b <= 4)
+                .filter_map(|(a, b)| date_year([&digits[..a], &digits[a..b], &digits[b..]]))
+                .min_by_key(|year| (year - this_year).abs());
+            if let Some(year) = year {
+                matches.push(Match {
+                    start: i,
+                    end: i + len,
+                    pattern: Pattern::Date,
+                    guesses: date_guesses(year, this_year),
+                });
+            }
+        }
+
+        // Dates with separators (e.g. 13/1/1987), which must be the same:
+        let digits_from = |from: usize| {
+            from + (chars[from..].iter())
+                .take(4)
+                .take_while(|c| c.is_ascii_digit())
+                .count()
+        };
+        let first = digits_from(i);
+        let Some(&separator) = chars.get(first).filter(|c| DATE_SEPARATORS.contains(c)) else {
+            continue;
+        };
+        let second = digits_from(first + 1);
+        if chars.get(second) != Some(&separator) {
+            continue;
+        }
+        let third = digits_from(second + 1);
+        let parts = [
+            &chars[i..first],
+            &chars[first + 1..second],
+            &chars[second + 1..third],
+        ];
+        if let Some(year) = date_year(parts).filter(|_| parts.iter().all(|p| !p.is_empty())) {
+            matches.push(Match {
+                start: i,
+                end: third,
+                pattern: Pattern::Date,
+                guesses: date_guesses(year, this_year) * 4.0,
+            });
+        }
+    }
+    matches
+}
+
+fn repeat_matches(chars: &[char], dictionaries: &Dictionaries, this_year: i32) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        // The repeated unit covering the most characters, the shortest one on ties:
+        let mut best: Option<(usize, usize)> = None;
+        for unit in 1..=(chars.len() - i) / 2 {
+            let base = &chars[i..i + unit];
+            let times = 1
+                + (chars[i + unit..].chunks_exact(unit))
+                    .take_while(|chunk| chunk == &base)
+                    .count();
+            if times >= 2 && best.is_none_or(|(u, t)| times * unit > u * t) {
+                best = Some((unit, times));
+            }
+        }
+
+        let Some((unit, times)) = best else {
+            i += 1;
+            continue;
+        };
+        let base = analyze_chars(&chars[i..i + unit], dictionaries, this_year);
+        matches.push(Match {
+            start: i,
+            end: i + unit * times,
+            pattern: Pattern::Repeat { times },
+            guesses: base.guesses * times as f64,
+        });
+        i += unit * times;
+    }
+    matches
+}
+
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let class = |c: char| match c {
+        'a'..='z' => Some(0),
+        'A'..='Z' => Some(1),
+        '0'..='9' => Some(2),
+        _ => None,
+    };
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + 1 < chars.len() {
+        let delta = chars[i + 1] as i32 - chars[i] as i32;
+        let mut j = i + 1;
+        while j + 1 < chars.len()
+            && chars[j + 1] as i32 - chars[j] as i32 == delta
+            && class(chars[j + 1]).is_some()
+            && class(chars[j + 1]) == class(chars[i])
+        {
+            j += 1;
+        }
+
+        let len = j - i + 1;
+        let same_class = class(chars[i]).is_some() && class(chars[i]) == class(chars[i + 1]);
+        if len >= 3 && same_class && (1..=5).contains(&delta.abs()) {
+            // Sequences starting at the obvious ends are tried first:
+            let start = match chars[i] {
+                'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9' => 4.0,
+                '0'..='9' => 10.0,
+                _ => 26.0,
+            };
+            let descending = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match {
+                start: i,
+                end: j + 1,
+                pattern: Pattern::Sequence,
+                guesses: start * len as f64 * descending,
+            });
+        }
+        i = j;
+    }
+    matches
+}
+
+// Picks the matches (filling the gaps by brute force) that make the password the easiest to
+// guess. Finding `l` parts in order takes l! times the product of their guesses, plus a
+// minimum for every part beyond the first:
+fn most_guessable(n: usize, matches: Vec<Match>) -> Analysis {
+    if n == 0 {
+        return Analysis {
+            guesses: 1.0,
+            matches: Vec::new(),
+        };
+    }
+
+    // The least product of guesses to cover the first `k` characters with `l` parts, and the
+    // last part (a match, or the start of a brute-forced one):
+    #[derive(Clone, Copy)]
+    enum Last {
+        Match(usize),
+        Bruteforce(usize),
+    }
+    let mut best: Vec<Vec<Option<(f64, Last)>>> = vec![vec![None; n + 1]; n + 1];
+    best[0][0] = Some((1.0, Last::Bruteforce(0)));
+    let bruteforce = |len: usize| {
+        BRUTEFORCE_CARDINALITY
+            .powi(len as i32)
+            .max(min_guesses(len, n) + 1.0)
+    };
+    let mut ending = vec![Vec::new(); n + 1];
+    for (index, m) in matches.iter().enumerate() {
+        ending[m.end].push(index);
+    }
+    for k in 1..=n {
+        for l in 1..=k {
+            let mut candidates: Vec<(f64, Last)> = Vec::new();
+            for &index in &ending[k] {
+                let m = &matches[index];
+                if let Some((product, _)) = best[m.start][l - 1] {
+                    candidates.push((product * m.guesses, Last::Match(index)));
+                }
+            }
+            for (start, covered) in best.iter().enumerate().take(k) {
+                if let Some((product, _)) = covered[l - 1] {
+                    candidates.push((product * bruteforce(k - start), Last::Bruteforce(start)));
+                }
+            }
+            best[k][l] = candidates.into_iter().min_by(|a, b| a.0.total_cmp(&b.0));
+        }
+    }
+
+    let total = |l: usize, product: f64| {
+        let factorial: f64 = (1..=l).map(|i| i as f64).product();
+        factorial * product + MIN_GUESSES_PER_PATTERN.powi(l as i32 - 1)
+    };
+    let (mut l, guesses) = (1..=n)
+        .filter_map(|l| Some((l, total(l, best[n][l]?.0))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((1, bruteforce(n)));
+
+    // Walk back through the chosen parts:
+    let mut matches: Vec<Option<Match>> = matches.into_iter().map(Some).collect();
+    let mut chosen = Vec::new();
+    let mut k = n;
+    while k > 0 {
+        let Some((_, last)) = best[k][l] else { break };
+        let m = match last {
+            Last::Match(index) => matches[index].take(),
+            Last::Bruteforce(start) => Some(Match {
+                start,
+                end: k,
+                pattern: Pattern::Bruteforce,
+                guesses: bruteforce(k - start),
+            }),
+        };
+        let Some(m) = m else { break };
+        k = m.start;
+        l -= 1;
+        chosen.push(m);
+    }
+    chosen.reverse();
+
+    Analysis {
+        guesses,
+        matches: chosen,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THIS_YEAR: i32 = 2024;
+
+    fn dictionaries() -> Dictionaries {
+        Dictionaries::new(
+            COMMON_PASSWORDS,
+            ENGLISH_WORDS,
+            "apple\nbanana\ncherry\ndragonfruit\nelderberry\nfig\ngrape\n",
+        )
+    }
+
+    fn bits(password: &str) -> f64 {
+        analyze(password, &dictionaries(), THIS_YEAR).bits()
+    }
+
+    // The single pattern the whole password was found to follow:
+    fn pattern(password: &str) -> Pattern {
+        let mut analysis = analyze(password, &dictionaries(), THIS_YEAR);
+        assert_eq!(
+            analysis.matches.len(),
+            1,
+            "{password} is not a single pattern"
+        );
+        let m = analysis.matches.remove(0);
+        assert_eq!((m.start, m.end), (0, password.chars().count()));
+        m.pattern
+    }
+
+    #[test]
+    fn common_passwords_are_weak() {
+        assert!(matches!(
+            pattern("password"),
+            Pattern::Dictionary {
+                rank: Some(2),
+                reversed: false,
+                l33t: false,
+                capitals: false,
+                ..
+            }
+        ));
+        assert!(bits("password") < 5.0);
+        assert!(bits("123456") < 5.0);
+    }
+
+    #[test]
+    fn variations_of_words_are_found() {
+        assert!(matches!(
+            pattern("P@ssw0rd"),
+            Pattern::Dictionary {
+                l33t: true,
+                capitals: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            pattern("drowssap"),
+            Pattern::Dictionary { reversed: true, .. }
+        ));
+        assert!(bits("P@ssw0rd") < 20.0);
+    }
+
+    #[test]
+    fn other_patterns_are_found() {
+        // (Walks not in the dictionaries, which would find them more cheaply:)
+        assert!(matches!(
+            pattern("sdfghjkl"),
+            Pattern::Keyboard { turns: 1 }
+        ));
+        assert!(matches!(pattern("wertgfds"), Pattern::Keyboard { .. }));
+        assert!(matches!(pattern("19/07/1987"), Pattern::Date));
+        assert!(matches!(pattern("1987"), Pattern::Year));
+        assert!(matches!(
+            pattern("zzzzzzzzzz"),
+            Pattern::Repeat { times: 10 }
+        ));
+        assert!(matches!(pattern("abcdefgh"), Pattern::Sequence));
+        for password in ["sdfghjkl", "19/07/1987", "zzzzzzzzzz", "abcdefgh"] {
+            assert!(bits(password) < 25.0, "{password} is too strong");
+        }
+    }
+
+    #[test]
+    fn matches_cover_the_password_in_order() {
+        let password = "Tr0ub4dor&3 qwerty 2024 xkcd";
+        let analysis = analyze(password, &dictionaries(), THIS_YEAR);
+        let mut end = 0;
+        for m in &analysis.matches {
+            assert_eq!(m.start, end);
+            end = m.end;
+        }
+        assert_eq!(end, password.chars().count());
+    }
+
+    #[test]
+    fn random_passwords_are_strong() {
+        assert!(bits("x7#Qp2!vL9wK") > 35.0);
+        assert!(bits("x7#Qp2!vL9wK") > bits("x7#Qp2!vL9"));
+    }
+
+    #[test]
+    fn generated_passwords_are_as_strong_as_their_words() {
+        // Three words of seven, plus the cost of every pattern after the first:
+        let guesses = analyze("grape fig apple", &dictionaries(), THIS_YEAR).guesses;
+        assert!(guesses >= 7f64.powi(3));
+        assert!(bits("grape fig apple") < bits("grape fig apple cherry"));
+    }
+
+    #[test]
+    fn empty_password_needs_one_guess() {
+        let analysis = analyze("", &dictionaries(), THIS_YEAR);
+        assert!(analysis.matches.is_empty());
+        assert_eq!(analysis.guesses, 1.0);
+    }
+
+    #[test]
+    fn shipped_dictionaries_are_small() {
+        assert!(dictionaries().small());
+    }
+}
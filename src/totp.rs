@@ -0,0 +1,244 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::config::TotpConfig;
+use crate::duration::unix_time;
+use crate::error::{Error, Result};
+use crate::storage::write_atomically;
+use data_encoding::BASE32_NOPAD;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use qrcode::{Color, QrCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::read,
+    io::{ErrorKind, Write},
+    sync::Mutex,
+};
+
+// RFC 6238 defaults, which every authenticator app supports:
+const PERIOD: u64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_LEN: usize = 20;
+// Codes from one period before or after are accepted too, to allow for clock drift:
+const DRIFT: u64 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Enrollment {
+    // Base32-encoded, as shown to the user:
+    secret: String,
+    // Whether the user has confirmed the enrollment with a valid code:
+    active: bool,
+    // The time step of the last code accepted, so it can't be used again:
+    last_step: u64,
+}
+
+// HOTP (RFC 4226) code for the given counter:
+fn code(secret: &[u8], counter: u64) -> Result<u32> {
+    let key = PKey::hmac(secret)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+    signer.update(&counter.to_be_bytes())?;
+    let hmac = signer.sign_to_vec()?;
+
+    // Dynamic truncation:
+    let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hmac[offset] & 0x7f,
+        hmac[offset + 1],
+        hmac[offset + 2],
+        hmac[offset + 3],
+    ]);
+    Ok(binary % 10u32.pow(DIGITS))
+}
+
+fn current_step() -> u64 {
+    unix_time().as_secs() / PERIOD
+}
+
+/// Renders the data as a QR code, in a PNG image.
+pub fn qr_png(data: &str) -> Result<Vec<u8>> {
+    // Pixels per module, and modules of blank margin around the code:
+    const SCALE: usize = 8;
+    const QUIET_ZONE: usize = 4;
+
+    let code = QrCode::new(data.as_bytes())
+        .map_err(|_| Error::InvalidOptions("data too long for a QR code"))?;
+    let width = code.width();
+    let colors = code.to_colors();
+    let side = (width + 2 * QUIET_ZONE) * SCALE;
+
+    let mut pixels = vec![0xff; side * side];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == Color::Dark {
+            let (x, y) = (i % width + QUIET_ZONE, i / width + QUIET_ZONE);
+            for row in y * SCALE..(y + 1) * SCALE {
+                pixels[row * side + x * SCALE..row * side + (x + 1) * SCALE].fill(0);
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|err| Error::io("Could not encode QR code")(err.into()))?;
+    Ok(png)
+}
+
+/// Time-based one-time passwords (RFC 6238), as a second factor for /pswd. Users enroll with
+/// an authenticator app, and confirm it with a first code before it is required.
+pub struct Totp {
+    config: TotpConfig,
+    enrollments: Mutex<HashMap<u64, Enrollment>>,
+}
+
+impl Totp {
+    /// Loads the enrollments saved in the configured store file, if any.
+    pub fn load(config: TotpConfig) -> Result<Self> {
+        let enrollments = match read(&config.store_file) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(Error::io("Could not read the TOTP store")(err)),
+        };
+        Ok(Self {
+            config,
+            enrollments: Mutex::new(enrollments),
+        })
+    }
+
+    /// Whether the user has to give a code before getting passwords.
+    pub fn enrolled(&self, user_id: u64) -> bool {
+        self.enrollments
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .is_some_and(|enrollment| enrollment.active)
+    }
+
+    /// Starts a new (unconfirmed) enrollment for the user, returning its `otpauth://` URI.
+    /// Returns `None` if the user is already enrolled.
+    pub fn enroll(&self, user_id: u64) -> Result<Option<String>> {
+        let mut enrollments = self.enrollments.lock().unwrap();
+        if enrollments.get(&user_id).is_some_and(|e| e.active) {
+            return Ok(None);
+        }
+
+        let mut secret = [0; SECRET_LEN];
+        rand_bytes(&mut secret)?;
+        let secret = BASE32_NOPAD.encode(&secret);
+        let uri = format!(
+            "otpauth://totp/Mother%20Brain:{user_id}?secret={secret}&issuer=Mother%20Brain&algorithm=SHA1&digits={DIGITS}&period={PERIOD}"
+        );
+        enrollments.insert(
+            user_id,
+            Enrollment {
+                secret,
+                active: false,
+                last_step: 0,
+            },
+        );
+        self.save(&enrollments)?;
+        Ok(Some(uri))
+    }
+
+    /// Checks a code of the user, confirming their enrollment if it wasn't yet. Every code
+    /// is only accepted once.
+    pub fn verify(&self, user_id: u64, code: &str) -> Result<bool> {
+        let mut enrollments = self.enrollments.lock().unwrap();
+        let Some(enrollment) = enrollments.get_mut(&user_id) else {
+            return Ok(false);
+        };
+        let Ok(code) = code.trim().parse::<u32>() else {
+            return Ok(false);
+        };
+        let secret = BASE32_NOPAD
+            .decode(enrollment.secret.as_bytes())
+            .map_err(|_| Error::InvalidOptions("malformed TOTP secret"))?;
+
+        let now = current_step();
+        for step in now.saturating_sub(DRIFT)..=now + DRIFT {
+            // Codes of steps up to the last one accepted have been used (or superseded):
+            if step > enrollment.last_step && self::code(&secret, step)? == code {
+                enrollment.last_step = step;
+                enrollment.active = true;
+                self.save(&enrollments)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Removes the user's enrollment, if the code is valid.
+    pub fn disable(&self, user_id: u64, code: &str) -> Result<bool> {
+        if !self.verify(user_id, code)? {
+            return Ok(false);
+        }
+        let mut enrollments = self.enrollments.lock().unwrap();
+        enrollments.remove(&user_id);
+        self.save(&enrollments)?;
+        Ok(true)
+    }
+
+    fn save(&self, enrollments: &HashMap<u64, Enrollment>) -> Result<()> {
+        let data = serde_json::to_vec(enrollments)?;
+        write_atomically(
+            &self.config.store_file,
+            "Could not write the TOTP store",
+            |file| file.write_all(&data),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The secret of the test vectors of RFC 4226 and RFC 6238 (for SHA-1):
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn code_matches_rfc_4226() {
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (counter, expected) in expected.into_iter().enumerate() {
+            assert_eq!(code(SECRET, counter as u64).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn code_matches_rfc_6238() {
+        // The RFC gives 8 digits; the last 6 are the same as with 6 digits:
+        let expected = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+        for (time, expected) in expected {
+            assert_eq!(code(SECRET, time / PERIOD).unwrap(), expected % 1_000_000);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+/*
+ *  Mother Brain: Discord bot for kinda securely generating kinda secure
+ *  passwords.
+ *  Copyright (C) 2023-2024  Bolu <bolu@tuta.io>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published
+ *  by the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::error::{Error, Result};
+use crate::storage::write_atomically;
+use openssl::rand::rand_bytes;
+use openssl::sha::Sha256;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+const VAULT_DIR: &str = "vaults";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+// Updates load, change and save a vault, so concurrent ones by the same user would overwrite
+// each other's changes. They take turns instead, with a lock for each user:
+static UPDATE_LOCKS: Mutex<BTreeMap<u64, Arc<Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// A password hint saved in a user's vault, together with the parameters needed to
+/// retrieve the same password again.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Entry {
+    pub hint: String,
+    pub strength: u32,
+    pub wordlist: String,
+    pub counter: u32,
+    pub notes: String,
+}
+
+// Every password key gets a vault file of its own, named after a hash of the key. Incorrect
+// secret words (which give the fake key) then open an empty vault of their own, instead of
+// failing to open the real one, so the vault never reveals whether the secret words were
+// correct, and a vault can't be created under a mistyped key in place of the real one.
+fn path(dir: &Path, pswd_key: &[u8], user_id: u64) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(b"mother-brain hint vault file");
+    hasher.update(pswd_key);
+    hasher.update(&user_id.to_le_bytes());
+    let name: String = hasher.finish()[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    dir.join(user_id.to_string()).join(format!("{name}.dat"))
+}
+
+// Derive a per-user vault key from the password key, so the vault can only be opened with
+// the same secret words that retrieve the passwords, and vaults of different users can't be
+// swapped around.
+fn key(pswd_key: &[u8], user_id: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"mother-brain hint vault");
+    hasher.update(pswd_key);
+    hasher.update(&user_id.to_le_bytes());
+    hasher.finish()
+}
+
+/// Loads the entries of the user's vault opened by the given password key. A vault that
+/// doesn't exist yet is empty.
+pub fn load(pswd_key: &[u8], user_id: u64) -> Result<Vec<Entry>> {
+    load_from(Path::new(VAULT_DIR), pswd_key, user_id)
+}
+
+fn load_from(dir: &Path, pswd_key: &[u8], user_id: u64) -> Result<Vec<Entry>> {
+    let data = match read(path(dir, pswd_key, user_id)) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::io("Could not read hint vault")(err)),
+    };
+    if data.len() < NONCE_LEN + TAG_LEN {
+        return Err(Error::InvalidVault);
+    }
+
+    // Vault layout: nonce || tag || ciphertext.
+    let (nonce, rest) = data.split_at(NONCE_LEN);
+    let (tag, ciphertext) = rest.split_at(TAG_LEN);
+    let plaintext = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key(pswd_key, user_id),
+        Some(nonce),
+        &[],
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| Error::InvalidVault)?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Loads the entries of the user's vault opened by the given password key, lets `change`
+/// modify them, and saves them back if it did. Updates by the same user take turns, so none
+/// is lost.
+pub fn update<T>(
+    pswd_key: &[u8],
+    user_id: u64,
+    change: impl FnOnce(&mut Vec<Entry>) -> T,
+) -> Result<T> {
+    update_in(Path::new(VAULT_DIR), pswd_key, user_id, change)
+}
+
+fn update_in<T>(
+    dir: &Path,
+    pswd_key: &[u8],
+    user_id: u64,
+    change: impl FnOnce(&mut Vec<Entry>) -> T,
+) -> Result<T> {
+    let lock = Arc::clone(UPDATE_LOCKS.lock().unwrap().entry(user_id).or_default());
+    let _guard = lock.lock().unwrap();
+
+    let mut entries = load_from(dir, pswd_key, user_id)?;
+    let before = entries.clone();
+    let result = change(&mut entries);
+    if entries != before {
+        save_to(dir, pswd_key, user_id, &entries)?;
+    }
+    Ok(result)
+}
+
+// Encrypts and stores the entries of the user's vault opened by the given password key,
+// replacing the previous contents:
+fn save_to(dir: &Path, pswd_key: &[u8], user_id: u64, entries: &[Entry]) -> Result<()> {
+    let plaintext = serde_json::to_vec(entries)?;
+
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key(pswd_key, user_id),
+        Some(&nonce),
+        &[],
+        &plaintext,
+        &mut tag,
+    )?;
+
+    let path = path(dir, pswd_key, user_id);
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir).map_err(Error::io("Could not create the hint vault directory"))?;
+    }
+    write_atomically(path, "Could not write hint vault", |file| {
+        file.write_all(&nonce)?;
+        file.write_all(&tag)?;
+        file.write_all(&ciphertext)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{remove_dir_all, write};
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mother-brain-vault-{name}"));
+        let _ = remove_dir_all(&dir);
+        dir
+    }
+
+    fn entry(hint: &str) -> Entry {
+        Entry {
+            hint: hint.to_string(),
+            strength: 6,
+            wordlist: "wordlist.txt".to_string(),
+            counter: 0,
+            notes: String::new(),
+        }
+    }
+
+    #[test]
+    fn saved_entries_load_back() {
+        let dir = dir("roundtrip");
+        update_in(&dir, b"key", 1, |entries| entries.push(entry("email"))).unwrap();
+        let entries = load_from(&dir, b"key", 1).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].hint, "email");
+    }
+
+    #[test]
+    fn wrong_key_opens_a_separate_empty_vault() {
+        let dir = dir("wrong-key");
+        update_in(&dir, b"key", 1, |entries| entries.push(entry("email"))).unwrap();
+        assert!(load_from(&dir, b"wrong key", 1).unwrap().is_empty());
+        // Nor can another user open it with the same key:
+        assert!(load_from(&dir, b"key", 2).unwrap().is_empty());
+
+        // Saving under the wrong key leaves the real vault alone:
+        update_in(&dir, b"wrong key", 1, |entries| entries.push(entry("bank"))).unwrap();
+        assert_eq!(load_from(&dir, b"key", 1).unwrap()[0].hint, "email");
+    }
+
+    #[test]
+    fn tampered_vault_is_rejected() {
+        let dir = dir("tampered");
+        update_in(&dir, b"key", 1, |entries| entries.push(entry("email"))).unwrap();
+        let path = path(&dir, b"key", 1);
+        let mut data = read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        write(&path, &data).unwrap();
+        assert!(matches!(
+            load_from(&dir, b"key", 1),
+            Err(Error::InvalidVault)
+        ));
+
+        write(&path, &data[..NONCE_LEN]).unwrap();
+        assert!(matches!(
+            load_from(&dir, b"key", 1),
+            Err(Error::InvalidVault)
+        ));
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let dir = dir("concurrent");
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    update_in(&dir, b"key", 1, |entries| {
+                        entries.push(entry(&format!("hint {i}")))
+                    })
+                    .unwrap()
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(load_from(&dir, b"key", 1).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn unchanged_vault_is_not_written() {
+        let dir = dir("unchanged");
+        update_in(&dir, b"key", 1, |entries| {
+            entries.retain(|e| e.hint != "email")
+        })
+        .unwrap();
+        assert!(!path(&dir, b"key", 1).exists());
+    }
+}